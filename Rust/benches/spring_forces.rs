@@ -0,0 +1,92 @@
+// spring forces for a large jittered grid, scalar against eight-wide, then adding them up per
+// point by gathering against scattering colour by colour. Run with `cargo bench`
+
+#[path = "../src/spring_kernel.rs"]
+#[allow(dead_code)]
+mod spring_kernel;
+
+use criterion::{ black_box, criterion_group, criterion_main, Criterion };
+use rand::Rng;
+use spring_kernel::{ PointArrays, SpringArrays };
+
+// points along each side of the grid
+const SIZE: usize = 256;
+
+// the gather is quadratic, so it gets a smaller grid
+const TOTALS_SIZE: usize = 64;
+
+// a `size` x `size` grid with springs along rows and columns, nudged off rest so every spring
+// is under some tension and a few are past the stiffening knee. Also the grid's four colours,
+// columns then rows each split by whether the spring starts on an even line, so no two springs
+// of a colour share a point
+fn grid(size: usize) -> (PointArrays, SpringArrays, Vec<Vec<usize>>) {
+    let mut rng = rand::thread_rng();
+    let mut points = PointArrays::default();
+    let mut springs = SpringArrays::default();
+    let mut colours = vec![Vec::new(); 4];
+
+    for i in 0..size {
+        for j in 0..size {
+            points.x.push((j as f32) + rng.gen_range(-0.1..0.1));
+            points.y.push((i as f32) + rng.gen_range(-0.1..0.1));
+            points.vx.push(rng.gen_range(-1.0..1.0));
+            points.vy.push(rng.gen_range(-1.0..1.0));
+            points.temperature.push(rng.gen_range(0.0..10.0));
+
+            let index = i * size + j;
+            let neighbours = [(i + 1 < size, index + size, i % 2), (j + 1 < size, index + 1, 2 + j % 2)];
+            for (_, other, colour) in neighbours.into_iter().filter(|(exists, _, _)| *exists) {
+                colours[colour].push(springs.p1.len());
+                springs.p1.push(index);
+                springs.p2.push(other);
+                springs.rest_length.push(1.0);
+                springs.spring_coeff.push(100.0);
+                springs.damp_coeff.push(0.5);
+                springs.stiffening_strain.push(0.05);
+                springs.stiffening_ratio.push(10.0);
+                springs.thermal_expansion.push(-0.002);
+            }
+        }
+    }
+
+    (points, springs, colours)
+}
+
+fn spring_forces(c: &mut Criterion) {
+    let (points, springs, _) = grid(SIZE);
+    let mut forces = vec![(0.0, 0.0); springs.p1.len()];
+
+    let mut group = c.benchmark_group("spring_forces");
+
+    group.bench_function("scalar", |b| {
+        b.iter(|| spring_kernel::spring_forces_scalar(black_box(&points), &springs, true, 0, &mut forces))
+    });
+
+    #[cfg(feature = "simd")]
+    group.bench_function("simd", |b| {
+        b.iter(|| spring_kernel::spring_forces_simd(black_box(&points), &springs, true, 0, &mut forces))
+    });
+
+    group.finish();
+}
+
+fn spring_totals(c: &mut Criterion) {
+    let (points, springs, colours) = grid(TOTALS_SIZE);
+    let mut forces = vec![(0.0, 0.0); springs.p1.len()];
+    spring_kernel::spring_forces_scalar(&points, &springs, false, 0, &mut forces);
+
+    let mut group = c.benchmark_group("spring_totals");
+
+    group.bench_function("gather", |b| {
+        b.iter(|| spring_kernel::gather(black_box(&points), &springs, &forces, false))
+    });
+
+    group.bench_function("coloured", |b| {
+        b.iter(|| spring_kernel::scatter(black_box(&points), &springs, &forces, &colours, false))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, spring_forces, spring_totals);
+criterion_main!(benches);
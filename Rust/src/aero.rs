@@ -0,0 +1,117 @@
+use rayon::prelude::*;
+use serde::{ Deserialize, Serialize };
+
+use crate::cloth::Point;
+
+// wind acting on the cloth as a set of thin panels, one per mesh triangle. The cloth lives in
+// the plane, so a triangle faces the wind through its edges: wind blowing across an edge is
+// caught and pushes the triangle downstream (drag), wind meeting it at an angle is also
+// deflected sideways (lift)
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Aerodynamics {
+    pub wind: (f32, f32),
+    pub density: f32,
+    pub drag_coeff: f32,
+    pub lift_coeff: f32,
+}
+
+impl Default for Aerodynamics {
+    fn default() -> Self {
+        Aerodynamics {
+            wind: (4.0, 0.0),
+            density: 0.05,
+            drag_coeff: 1.0,
+            lift_coeff: 0.5,
+        }
+    }
+}
+
+impl Aerodynamics {
+    // force on every point, each triangle's force split evenly between its three corners
+    pub fn forces(&self, points: &[Point], triangles: &[[usize; 3]]) -> Vec<(f32, f32)> {
+        let triangle_forces: Vec<(f32, f32)> = triangles
+            .par_iter()
+            .map(|triangle| self.triangle_force(triangle.map(|index| &points[index])))
+            .collect();
+
+        let mut forces = vec![(0.0, 0.0); points.len()];
+        for (triangle, (fx, fy)) in triangles.iter().zip(triangle_forces) {
+            for &index in triangle {
+                forces[index].0 += fx / 3.0;
+                forces[index].1 += fy / 3.0;
+            }
+        }
+
+        forces
+    }
+
+    // every edge counts in proportion to its length, so the force only depends on the
+    // triangle's shape and not on which corner the mesh happened to list first
+    fn triangle_force(&self, corners: [&Point; 3]) -> (f32, f32) {
+        let [a, b, c] = corners;
+
+        // wind as seen by the triangle
+        let vx = self.wind.0 - (a.vx + b.vx + c.vx) / 3.0;
+        let vy = self.wind.1 - (a.vy + b.vy + c.vy) / 3.0;
+        let speed = (vx * vx + vy * vy).sqrt();
+
+        if speed == 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let (mut perimeter, mut facing, mut turning) = (0.0, 0.0, 0.0);
+        for k in 0..3 {
+            let (p, q) = (corners[k], corners[(k + 1) % 3]);
+            let tx = q.x - p.x;
+            let ty = q.y - p.y;
+            let chord = (tx * tx + ty * ty).sqrt();
+
+            if chord == 0.0 {
+                continue;
+            }
+
+            // cosines of the wind against the edge's normal and along the edge, the product
+            // is the same whichever way round the edge runs
+            let across = (vx * -ty + vy * tx) / (speed * chord);
+            let along = (vx * tx + vy * ty) / (speed * chord);
+
+            perimeter += chord;
+            facing += chord * across.abs();
+            turning += chord * across * along;
+        }
+
+        if perimeter == 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let area = 0.5 * ((b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)).abs();
+        let pressure = 0.5 * self.density * area * speed * speed;
+
+        // drag along the wind, lift perpendicular to it
+        let drag = (pressure * self.drag_coeff * facing) / perimeter;
+        let lift = (pressure * self.lift_coeff * turning) / perimeter;
+
+        let (ux, uy) = (vx / speed, vy / speed);
+        (drag * ux - lift * uy, drag * uy + lift * ux)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cloth::Cloth;
+
+    #[test]
+    fn force_does_not_depend_on_corner_order() {
+        let cloth = Cloth::new(2, 2);
+        let [a, b, c] = [&cloth.points[0], &cloth.points[1], &cloth.points[2]];
+        let aerodynamics = Aerodynamics { wind: (3.0, 1.0), ..Aerodynamics::default() };
+
+        let force = aerodynamics.triangle_force([a, b, c]);
+        for corners in [[b, c, a], [c, a, b], [a, c, b]] {
+            let other = aerodynamics.triangle_force(corners);
+            assert!((force.0 - other.0).abs() < 1e-6 && (force.1 - other.1).abs() < 1e-6);
+        }
+        assert!(force.0 > 0.0);
+    }
+}
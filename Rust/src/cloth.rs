@@ -1,7 +1,21 @@
 use rand::Rng;
 use crate::CORE_COUNT;
+use crate::aero::Aerodynamics;
+use crate::fem::{ Fem, ForceModel, DEFAULT_POISSON_RATIO, DEFAULT_VISCOSITY, DEFAULT_YOUNGS_MODULUS };
+use crate::rigid::FLOOR_Y;
+use crate::material::Material;
+use crate::mesh::Mesh;
+use crate::pressure::Pressure;
+use crate::shape_matching::ShapeMatching;
+use crate::spring_kernel::{ self, PointArrays, SpringArrays };
 use rayon::prelude::*;
-#[derive(Clone, Copy, PartialEq, Debug)]
+use serde::{ de::DeserializeOwned, Deserialize, Serialize };
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Point {
     pub x: f32,
     pub y: f32,
@@ -11,254 +25,1096 @@ pub struct Point {
     pub ay: f32,
     pub fixed: bool,
     pub ext_m: f32,
+    #[serde(default = "default_mass")]
+    pub mass: f32,
+    // degrees above ambient
+    #[serde(default)]
+    pub temperature: f32,
 }
 
-#[derive(Clone, Copy)]
+// mass every point starts with, 1cm of cloth
+pub const DEFAULT_MASS: f32 = 0.01;
+
+fn default_mass() -> f32 {
+    DEFAULT_MASS
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct Spring {
-    pub p1: (usize, usize),
-    pub p2: (usize, usize),
+    pub p1: usize,
+    pub p2: usize,
     pub rest_length: f32,
     pub spring_coeff: f32,
     pub damp_coeff: f32,
+    // strain past which the spring gets `stiffening_ratio` times stiffer, see `Material`
+    #[serde(default = "default_stiffening_strain")]
+    pub stiffening_strain: f32,
+    #[serde(default = "default_stiffening_ratio")]
+    pub stiffening_ratio: f32,
+    // joins second neighbours to resist bending, keeps its own stiffness when the material
+    // changes and isn't drawn
+    #[serde(default)]
+    pub bending: bool,
+    // strain past which the rest length creeps towards the current length, permanently
+    #[serde(default = "default_yield_strain")]
+    pub yield_strain: f32,
+    // fraction of the strain past yield given up per second
+    #[serde(default)]
+    pub plastic_flow: f32,
+    // total change of rest length so far, relative to the rest length at the time
+    #[serde(default)]
+    pub plastic_strain: f32,
+    // relative change of rest length per degree the ends are above ambient, negative for
+    // fibres that shrink when heated
+    #[serde(default)]
+    pub thermal_expansion: f32,
+    // runs closer to vertical than horizontal in the mesh it was built from, so it takes the
+    // material's warp properties rather than the weft ones however the cloth moves later
+    #[serde(default)]
+    pub warp: bool,
 }
 
-#[derive(Clone)]
-pub struct Cloth {
-    pub points: Vec<Vec<Point>>,
-    pub springs: Vec<Spring>,
+fn default_yield_strain() -> f32 {
+    f32::MAX
+}
+
+fn default_stiffening_strain() -> f32 {
+    f32::MAX
+}
+
+fn default_stiffening_ratio() -> f32 {
+    1.0
+}
+
+impl Spring {
+    // unstretched at `rest_length`, with no stiffness until a material is applied
+    pub fn new(p1: usize, p2: usize, rest_length: f32) -> Self {
+        Spring {
+            p1,
+            p2,
+            rest_length,
+            spring_coeff: 0.0,
+            damp_coeff: 0.0,
+            stiffening_strain: default_stiffening_strain(),
+            stiffening_ratio: default_stiffening_ratio(),
+            bending: false,
+            yield_strain: default_yield_strain(),
+            plastic_flow: 0.0,
+            plastic_strain: 0.0,
+            thermal_expansion: 0.0,
+            warp: false,
+        }
+    }
+
+    // pull along the spring for a given length, linear up to the knee and steeper past it
+    pub fn tension(&self, distance: f32) -> f32 {
+        let extension = distance - self.rest_length;
+        let knee = self.stiffening_strain * self.rest_length;
+
+        if extension <= knee {
+            self.spring_coeff * extension
+        } else {
+            self.spring_coeff * (knee + (extension - knee) * self.stiffening_ratio)
+        }
+    }
+
+    // integral of `tension` from the rest length
+    pub fn energy(&self, distance: f32) -> f32 {
+        let extension = distance - self.rest_length;
+        let knee = self.stiffening_strain * self.rest_length;
+
+        if extension <= knee {
+            0.5 * self.spring_coeff * extension * extension
+        } else {
+            let past = extension - knee;
+            self.spring_coeff * (0.5 * knee * knee + knee * past + 0.5 * self.stiffening_ratio * past * past)
+        }
+    }
+
+    // how much longer the spring is at rest at the average temperature of its ends
+    pub fn thermal_scale(&self, p1: &Point, p2: &Point) -> f32 {
+        let temperature = (p1.temperature + p2.temperature) / 2.0;
+        // never shrink to nothing however hot it gets
+        (1.0 + self.thermal_expansion * temperature).max(0.1)
+    }
+
+    pub fn heated(&self, p1: &Point, p2: &Point) -> Spring {
+        Spring { rest_length: self.rest_length * self.thermal_scale(p1, p2), ..*self }
+    }
+
+    // spring and damper force on one end of the spring, `on_p1` picks which
+    pub fn force(&self, p1: &Point, p2: &Point, on_p1: bool, damping: Damping) -> (f32, f32) {
+        let dx = p2.x - p1.x;
+        let dy = p2.y - p1.y;
+
+        let distance = (dx * dx + dy * dy).sqrt();
+        let magnitude = self.heated(p1, p2).tension(distance);
+
+        let spring_force_x = (magnitude * dx) / distance;
+        let spring_force_y = (magnitude * dy) / distance;
+
+        match damping {
+            Damping::Relative => {
+                // how fast the ends are separating, damped along the spring only
+                let separating = ((p2.vx - p1.vx) * dx + (p2.vy - p1.vy) * dy) / distance;
+                let damping_force_x = (self.damp_coeff * separating * dx) / distance;
+                let damping_force_y = (self.damp_coeff * separating * dy) / distance;
+
+                if on_p1 {
+                    (spring_force_x + damping_force_x, spring_force_y + damping_force_y)
+                } else {
+                    (-spring_force_x - damping_force_x, -spring_force_y - damping_force_y)
+                }
+            }
+            Damping::Legacy => {
+                let point = if on_p1 { p1 } else { p2 };
+                let damping_force_x = -point.vx * self.damp_coeff;
+                let damping_force_y = -point.vy * self.damp_coeff;
+
+                if on_p1 {
+                    (spring_force_x + damping_force_x, spring_force_y + damping_force_y)
+                } else {
+                    (-spring_force_x + damping_force_x, -spring_force_y + damping_force_y)
+                }
+            }
+        }
+    }
+
+    // let the rest length give way if the spring is stretched or squashed past yield. Rate
+    // independent plasticity would snap straight to the yield surface, flowing towards it over
+    // time instead keeps a fast jolt from crumpling everything at once
+    pub fn yield_to(&mut self, distance: f32, dt: f32) {
+        let strain = (distance - self.rest_length) / self.rest_length;
+        let excess = strain.abs() - self.yield_strain;
+        if excess <= 0.0 {
+            return;
+        }
+
+        let flow = (self.plastic_flow * dt).min(1.0) * excess * strain.signum();
+        self.rest_length += flow * self.rest_length;
+        self.plastic_strain += flow.abs();
+    }
+
+    // stiffness at the steepest part of the curve
+    pub fn max_stiffness(&self) -> f32 {
+        self.spring_coeff * self.stiffening_ratio.max(1.0)
+    }
+}
+
+// how spring dampers act. `Legacy` drags each end by its own absolute velocity, which also
+// slows a cloth that is just falling, and is kept to reproduce the original lab results
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Damping {
+    #[default]
+    Relative,
+    Legacy,
+}
+
+// about the drag the legacy dampers put on a grid point through its four springs, any less
+// and the default cloth falls fast enough to overstretch its springs
+pub const DEFAULT_AIR_DRAG: f32 = 0.1;
+
+fn default_air_drag() -> f32 {
+    DEFAULT_AIR_DRAG
+}
+
+pub const DEFAULT_CONDUCTIVITY: f32 = 1.0;
+
+fn default_conductivity() -> f32 {
+    DEFAULT_CONDUCTIVITY
+}
+
+pub const DEFAULT_COOLING: f32 = 0.1;
+
+fn default_cooling() -> f32 {
+    DEFAULT_COOLING
+}
+
+// the non-spring forces on every point, bundled so the chunked solver can take them by value
+#[derive(Clone, Copy, Debug)]
+pub struct Environment {
     pub g: f32,
-    pub m: f32,
     pub g_on: bool,
+    pub damping: Damping,
+    pub air_drag: f32,
 }
 
-impl Cloth {
-    pub fn new(n: usize, m: usize) -> Self {
-        let mut points =
-            vec![vec![Point {x:0.0, y:0.0, vx:0.0, vy:0.0, ax:0.0, ay:0.0, fixed: false, ext_m:0.0}; m]; n];
-        for i in 0..n {
-            for j in 0..m {
-                points[i][j] = Point {
-                    x: j as f32,
-                    y: i as f32,
-                    vx: 0.0,
-                    vy: 0.0,
-                    ax: 0.0,
-                    ay: 0.0,
-                    fixed: false,
-                    ext_m: 0.0,
-                };
+impl Environment {
+    // gravity plus air drag, which only applies with relative damping since the legacy
+    // dampers already act as drag
+    fn force(&self, point: &Point) -> (f32, f32) {
+        let gravity_force_y = if self.g_on { -self.g * point.mass } else { 0.0 };
+
+        match self.damping {
+            Damping::Relative => {
+                (-self.air_drag * point.vx, gravity_force_y - self.air_drag * point.vy)
             }
+            Damping::Legacy => (0.0, gravity_force_y),
         }
+    }
+}
 
-        // make the top left and top right points fixed
-        points[n - 1][0].fixed = true;
-        points[n - 1][m - 1].fixed = true;
-
-        let mut springs = Vec::new();
-        for i in 0..n {
-            for j in 0..m {
-                if i < n - 1 {
-                    springs.push(Spring {
-                        p1: (i, j),
-                        p2: (i + 1, j),
-                        spring_coeff: 10.0,
-                        damp_coeff: 0.03,
-                        rest_length: 1.0,
-                    });
-                }
+// whole-cloth energy and momentum, to see whether the integrator is gaining energy
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Diagnostics {
+    pub kinetic: f32,
+    pub potential: f32,
+    pub elastic: f32,
+    pub momentum: (f32, f32),
+    pub max_strain: f32,
+}
 
-                if j < m - 1 {
-                    springs.push(Spring {
-                        p1: (i, j),
-                        p2: (i, j + 1),
-                        spring_coeff: 10.0,
-                        damp_coeff: 0.03,
-                        rest_length: 1.0,
-                    });
-                }
+impl Diagnostics {
+    pub fn total_energy(&self) -> f32 {
+        self.kinetic + self.potential + self.elastic
+    }
+}
+
+// why a step was rejected, pointing at the first thing that went wrong
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SimError {
+    // position or velocity of this point became NaN or infinite
+    NonFinite {
+        point: usize,
+    },
+    // a spring stretched past `Cloth::strain_limit`, elastic energy grows with strain squared
+    // so this catches an energy blow-up before it turns into NaNs
+    Overstretched {
+        spring: usize,
+        strain: f32,
+    },
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimError::NonFinite { point } => {
+                write!(f, "point {} has a non-finite position or velocity", point)
+            }
+            SimError::Overstretched { spring, strain } => {
+                write!(f, "spring {} is overstretched (strain {})", spring, strain)
+            }
+        }
+    }
+}
+
+impl Error for SimError {}
+
+// how a frame's worth of time was actually stepped, `dt` is the smallest substep used
+#[derive(Clone, Copy, Debug)]
+pub struct StepStats {
+    pub dt: f32,
+    pub substeps: usize,
+}
+
+impl StepStats {
+    fn merge(self, other: StepStats) -> StepStats {
+        StepStats {
+            dt: self.dt.min(other.dt),
+            substeps: self.substeps + other.substeps,
+        }
+    }
+}
+
+// fraction of the stability limits actually used by `stable_dt`
+const DT_SAFETY: f32 = 0.5;
+
+// never step further than this even when the cloth is soft and slow
+const MAX_DT: f32 = 0.01;
+
+// passes over the springs per step when the cloth is inextensible
+const LENGTH_ITERATIONS: usize = 20;
+
+// springs per parallel task in the multithreaded step, a multiple of the SIMD width
+const SPRING_CHUNK: usize = 1024;
+
+fn default_strain_limit() -> f32 {
+    10.0
+}
+
+// how the multithreaded step adds up spring forces per point
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum SpringParallelism {
+    // each point picks its springs out of the whole list
+    #[default]
+    Gather,
+    // springs are coloured so that each colour can add into its points in parallel
+    Coloured,
+}
+
+// springs split into colours so that no two springs of a colour share a point, along with the
+// endpoints it was worked out for so a step can tell when the springs have been rewired
+#[derive(Clone, Debug, Default)]
+struct Colouring {
+    p1: Vec<usize>,
+    p2: Vec<usize>,
+    num_points: usize,
+    colours: Vec<Vec<usize>>,
+}
+
+impl Colouring {
+    // greedy, each spring takes the lowest colour neither of its ends has yet, which needs at
+    // most one less than twice the most springs on any point
+    fn new(springs: &SpringArrays, num_points: usize) -> Self {
+        let mut point_colours: Vec<Vec<usize>> = vec![Vec::new(); num_points];
+        let mut colours: Vec<Vec<usize>> = Vec::new();
+
+        for (i, (&a, &b)) in springs.p1.iter().zip(&springs.p2).enumerate() {
+            let taken = |colour: &usize| point_colours[a].contains(colour) || point_colours[b].contains(colour);
+            let colour = (0..).find(|colour| !taken(colour)).unwrap();
+
+            if colour == colours.len() {
+                colours.push(Vec::new());
             }
+            colours[colour].push(i);
+            point_colours[a].push(colour);
+            point_colours[b].push(colour);
         }
 
-        Cloth {
+        Colouring { p1: springs.p1.clone(), p2: springs.p2.clone(), num_points, colours }
+    }
+
+    // whether this was worked out for exactly these endpoints
+    fn matches(&self, springs: &SpringArrays, num_points: usize) -> bool {
+        self.num_points == num_points && self.p1 == springs.p1 && self.p2 == springs.p2
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Cloth {
+    pub points: Vec<Point>,
+    pub springs: Vec<Spring>,
+    // three point indices each, only the aerodynamics uses them
+    #[serde(default)]
+    pub triangles: Vec<[usize; 3]>,
+    pub g: f32,
+    pub g_on: bool,
+    #[serde(default = "default_strain_limit")]
+    pub strain_limit: f32,
+    #[serde(default)]
+    pub damping: Damping,
+    // linear drag on every point's absolute velocity, only used with `Damping::Relative`
+    #[serde(default = "default_air_drag")]
+    pub air_drag: f32,
+    // wind on the cloth's triangles, off when `None`
+    #[serde(default)]
+    pub aerodynamics: Option<Aerodynamics>,
+    // gas inside a closed loop of the cloth's points, for balloons and blobs
+    #[serde(default)]
+    pub pressure: Option<Pressure>,
+    // groups of points pulled back into their rest shape after every step
+    #[serde(default)]
+    pub shape_matching: Option<ShapeMatching>,
+    // finite elements over the triangles, only used with `ForceModel::Fem`
+    #[serde(default)]
+    pub fem: Option<Fem>,
+    #[serde(default)]
+    pub force_model: ForceModel,
+    // line colour when drawn alongside other cloths
+    #[serde(default = "default_color")]
+    pub color: [f32; 4],
+    // hold every non-bending spring at its rest length after each step, e.g. a chain
+    #[serde(default)]
+    pub inextensible: bool,
+    // how fast temperature differences even out along a spring, per second
+    #[serde(default = "default_conductivity")]
+    pub conductivity: f32,
+    // how fast every point cools back to ambient, per second
+    #[serde(default = "default_cooling")]
+    pub cooling: f32,
+    #[serde(default)]
+    pub spring_parallelism: SpringParallelism,
+    // worked out again whenever a spring's ends change
+    #[serde(skip)]
+    spring_colouring: Colouring,
+}
+
+fn default_color() -> [f32; 4] {
+    [0.0, 0.0, 0.0, 1.0]
+}
+
+impl Cloth {
+    pub fn new(n: usize, m: usize) -> Self {
+        Cloth::with_material(n, m, &Material::default())
+    }
+
+    // an n x m grid with warp springs along the columns and weft springs along the rows, hung
+    // from its top corners
+    pub fn with_material(n: usize, m: usize, material: &Material) -> Self {
+        let mut cloth = Cloth::from_mesh(&Mesh::grid(n, m, 1.0), material);
+        cloth.pin_top_corners();
+        cloth
+    }
+
+    // a spring for every edge and an element for every triangle, at rest in the mesh's shape.
+    // Nothing is pinned
+    pub fn from_mesh(mesh: &Mesh, material: &Material) -> Self {
+        let points = mesh.positions
+            .iter()
+            .map(|&[x, y]| Point {
+                x,
+                y,
+                vx: 0.0,
+                vy: 0.0,
+                ax: 0.0,
+                ay: 0.0,
+                fixed: false,
+                ext_m: 0.0,
+                mass: DEFAULT_MASS,
+                temperature: 0.0,
+            })
+            .collect();
+
+        let springs = mesh.edges
+            .iter()
+            .map(|&(p1, p2)| {
+                let [x1, y1] = mesh.positions[p1];
+                let [x2, y2] = mesh.positions[p2];
+
+                let mut spring = Spring::new(p1, p2, ((x2 - x1) * (x2 - x1) + (y2 - y1) * (y2 - y1)).sqrt());
+                spring.warp = (y2 - y1).abs() > (x2 - x1).abs();
+                spring
+            })
+            .collect();
+
+        // elements over the same rest shape, ready for `ForceModel::Fem`
+        let fem = (!mesh.triangles.is_empty()).then(|| {
+            Fem::new(&mesh.positions, &mesh.triangles, DEFAULT_YOUNGS_MODULUS, DEFAULT_POISSON_RATIO, DEFAULT_VISCOSITY)
+        });
+
+        let mut cloth = Cloth {
             points,
             springs,
+            triangles: mesh.triangles.clone(),
             g: 9.81,
-            m: 0.01,
             g_on: true,
+            strain_limit: default_strain_limit(),
+            damping: Damping::default(),
+            air_drag: DEFAULT_AIR_DRAG,
+            aerodynamics: None,
+            pressure: None,
+            shape_matching: None,
+            fem,
+            force_model: ForceModel::default(),
+            color: default_color(),
+            inextensible: false,
+            conductivity: DEFAULT_CONDUCTIVITY,
+            cooling: DEFAULT_COOLING,
+            spring_parallelism: SpringParallelism::default(),
+            spring_colouring: Colouring::default(),
+        };
+        cloth.apply_material(material);
+
+        cloth
+    }
+
+    // fix the leftmost and rightmost of the highest points, the same point if there is only one
+    pub fn pin_top_corners(&mut self) {
+        let top = self.points.iter().map(|point| point.y).fold(f32::MIN, f32::max);
+        let on_top = |point: &&Point| point.y >= top - 1e-3;
+
+        let left = self.points
+            .iter()
+            .enumerate()
+            .filter(|(_, point)| on_top(point))
+            .min_by(|(_, p1), (_, p2)| p1.x.total_cmp(&p2.x))
+            .map(|(index, _)| index);
+        let right = self.points
+            .iter()
+            .enumerate()
+            .filter(|(_, point)| on_top(point))
+            .max_by(|(_, p1), (_, p2)| p1.x.total_cmp(&p2.x))
+            .map(|(index, _)| index);
+
+        for index in [left, right].into_iter().flatten() {
+            self.points[index].fixed = true;
         }
     }
 
-    pub fn simulate(&mut self, dt: f32) {
-        let mut forces = vec![vec![(0.0, 0.0); self.points[0].len()]; self.points.len()];
+    // swap the fabric without disturbing the current shape or velocities
+    pub fn apply_material(&mut self, material: &Material) {
+        for spring in self.springs.iter_mut().filter(|spring| !spring.bending) {
+            material.apply(spring);
+        }
+    }
 
-        for (i, row) in self.points.iter().enumerate() {
-            for (j, point) in row.iter().enumerate() {
-                let mut total_force_x = 0.0;
-                let mut total_force_y = 0.0;
+    pub fn environment(&self) -> Environment {
+        Environment {
+            g: self.g,
+            g_on: self.g_on,
+            damping: self.damping,
+            air_drag: self.air_drag,
+        }
+    }
 
-                //spring and damper
-                for spring in &self.springs {
-                    let point1 = &self.points[spring.p1.0][spring.p1.1];
-                    let point2 = &self.points[spring.p2.0][spring.p2.1];
+    // per point wind and gas pressure force, all zero while there is neither
+    fn field_forces(&self) -> Vec<(f32, f32)> {
+        let mut forces = match &self.aerodynamics {
+            Some(aerodynamics) => aerodynamics.forces(&self.points, &self.triangles),
+            None => vec![(0.0, 0.0); self.points.len()],
+        };
 
-                    let dx = point2.x - point1.x;
-                    let dy = point2.y - point1.y;
+        if let Some(pressure) = &self.pressure {
+            for (force, (fx, fy)) in forces.iter_mut().zip(pressure.forces(&self.points)) {
+                force.0 += fx;
+                force.1 += fy;
+            }
+        }
 
-                    let distance = (dx * dx + dy * dy).sqrt();
-                    let magnitude = spring.spring_coeff * (distance - spring.rest_length);
+        if let Some(fem) = self.active_fem() {
+            for (force, (fx, fy)) in forces.iter_mut().zip(fem.forces(&self.points)) {
+                force.0 += fx;
+                force.1 += fy;
+            }
+        }
+
+        forces
+    }
+
+    // the elements when they replace the springs
+    fn active_fem(&self) -> Option<&Fem> {
+        match self.force_model {
+            ForceModel::Springs => None,
+            ForceModel::Fem => self.fem.as_ref(),
+        }
+    }
+
+    // the springs that push and pull, none while the elements stand in for them
+    fn active_springs(&self) -> &[Spring] {
+        if self.active_fem().is_some() { &[] } else { &self.springs }
+    }
+
+    pub fn set_mass(&mut self, mass: f32) {
+        for point in &mut self.points {
+            point.mass = mass;
+        }
+    }
 
-                    let spring_force_x = (magnitude * dx) / distance;
-                    let spring_force_y = (magnitude * dy) / distance;
+    // the lowest points of the cloth, e.g. a weighted hem
+    pub fn set_hem_mass(&mut self, mass: f32) {
+        let bottom = self.points.iter().map(|point| point.y).fold(f32::MAX, f32::min);
+        self.set_region_mass([f32::MIN, bottom - 1e-3], [f32::MAX, bottom + 1e-3], mass);
+    }
+
+    // every point inside the box from `min` to `max`
+    pub fn set_region_mass(&mut self, min: [f32; 2], max: [f32; 2], mass: f32) {
+        for point in &mut self.points {
+            let inside = (min[0]..=max[0]).contains(&point.x) && (min[1]..=max[1]).contains(&point.y);
+            if inside {
+                point.mass = mass;
+            }
+        }
+    }
 
-                    let damping_force_x = -point.vx * spring.damp_coeff;
-                    let damping_force_y = -point.vy * spring.damp_coeff;
+    // snapshot the whole cloth to disk, as JSON for a .json path and bincode otherwise
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        write_snapshot(path.as_ref(), self)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let cloth: Cloth = read_snapshot(path.as_ref())?;
+        cloth.validate().map_err(invalid_data)?;
+
+        Ok(cloth)
+    }
+
+    // a loaded cloth must have points, and everything that refers to them by index has to stay
+    // in range or the first step panics
+    pub fn validate(&self) -> Result<(), String> {
+        let num_points = self.points.len();
+        if num_points == 0 {
+            return Err("snapshot contains no points".to_string());
+        }
 
-                    if point1.x == point.x && point1.y == point.y {
-                        total_force_x += spring_force_x + damping_force_x;
-                        total_force_y += spring_force_y + damping_force_y;
-                    } else if point2.x == point.x && point2.y == point.y {
-                        total_force_x -= spring_force_x - damping_force_x;
-                        total_force_y -= spring_force_y - damping_force_y;
-                    }
+        check_indices("a spring", self.springs.iter().flat_map(|spring| [spring.p1, spring.p2]), num_points)?;
+        // has no direction to pull in, and would share a point with itself when coloured
+        if let Some(index) = self.springs.iter().position(|spring| spring.p1 == spring.p2) {
+            return Err(format!("spring {} joins point {} to itself", index, self.springs[index].p1));
+        }
+        check_indices("a triangle", self.triangles.iter().flatten().copied(), num_points)?;
+
+        if let Some(pressure) = &self.pressure {
+            check_indices("the pressure outline", pressure.outline.iter().copied(), num_points)?;
+        }
+
+        if let Some(shape_matching) = &self.shape_matching {
+            for group in &shape_matching.groups {
+                if group.rest.len() != group.indices.len() {
+                    return Err("a shape matching group needs one rest position per point".to_string());
                 }
+                check_indices("a shape matching group", group.indices.iter().copied(), num_points)?;
+            }
+        }
 
-                //gravity
-                let gravity_force_x = 0.0;
-                let gravity_force_y = if self.g_on { -self.g * self.m } else { 0.0 };
+        if let Some(fem) = &self.fem {
+            check_indices("a finite element", fem.elements.iter().flat_map(|element| element.indices), num_points)?;
+        }
+
+        Ok(())
+    }
+
+    // flat list of point positions in vertex order
+    pub fn positions(&self) -> Vec<[f32; 2]> {
+        self.points
+            .iter()
+            .map(|point| [point.x, point.y])
+            .collect()
+    }
 
-                //external forces
-                let mut rng = rand::thread_rng();
-                let ext_force_x = rng.gen_range(-1.0..1.0) * point.ext_m;
-                let ext_force_y = rng.gen_range(-1.0..1.0) * point.ext_m;
+    // line-list indices for the springs, one pair of flat vertex indices per spring. Bending
+    // springs only double up on the others so they are left out
+    pub fn spring_indices(&self) -> Vec<u32> {
+        let indices: Vec<u32> = self.springs
+            .iter()
+            .filter(|spring| !spring.bending)
+            .flat_map(|spring| [spring.p1 as u32, spring.p2 as u32])
+            .collect();
 
-                //total
-                total_force_x += gravity_force_x + ext_force_x;
-                total_force_y += gravity_force_y + ext_force_y;
+        debug_assert!(
+            indices.iter().all(|&i| (i as usize) < self.points.len()),
+            "spring index out of range"
+        );
 
-                forces[i][j] = (total_force_x, total_force_y);
+        indices
+    }
+
+    // per point, the most any spring attached to it has deformed for good
+    pub fn plastic_strain(&self) -> Vec<f32> {
+        let mut strain = vec![0.0f32; self.points.len()];
+        for spring in &self.springs {
+            strain[spring.p1] = strain[spring.p1].max(spring.plastic_strain);
+            strain[spring.p2] = strain[spring.p2].max(spring.plastic_strain);
+        }
+
+        strain
+    }
+
+    pub fn diagnostics(&self) -> Diagnostics {
+        let mut diagnostics = Diagnostics::default();
+
+        for point in &self.points {
+            diagnostics.kinetic += 0.5 * point.mass * (point.vx * point.vx + point.vy * point.vy);
+
+            // gravity does no work while it's off, so it contributes no potential either
+            if self.g_on {
+                diagnostics.potential += point.mass * self.g * point.y;
             }
+
+            diagnostics.momentum.0 += point.mass * point.vx;
+            diagnostics.momentum.1 += point.mass * point.vy;
         }
 
-        for (i, row) in self.points.iter_mut().enumerate() {
-            for (j, point) in row.iter_mut().enumerate() {
-                if point.fixed {
-                    continue;
+        for spring in &self.springs {
+            let point1 = &self.points[spring.p1];
+            let point2 = &self.points[spring.p2];
+
+            let dx = point2.x - point1.x;
+            let dy = point2.y - point1.y;
+            let distance = (dx * dx + dy * dy).sqrt();
+            let extension = distance - spring.rest_length;
+
+            diagnostics.elastic += spring.heated(point1, point2).energy(distance);
+            diagnostics.max_strain = diagnostics.max_strain.max(extension.abs() / spring.rest_length);
+        }
+
+        // the springs only measure strain while the elements carry the load
+        if let Some(fem) = self.active_fem() {
+            diagnostics.elastic = fem.energy(&self.points);
+        }
+
+        diagnostics
+    }
+
+    // looks for the first diverged point, then the worst overstretched spring
+    pub fn check_stability(&self) -> Result<(), SimError> {
+        for (index, point) in self.points.iter().enumerate() {
+            let finite =
+                point.x.is_finite() &&
+                point.y.is_finite() &&
+                point.vx.is_finite() &&
+                point.vy.is_finite();
+
+            if !finite {
+                return Err(SimError::NonFinite { point: index });
+            }
+        }
+
+        let worst = self.springs
+            .iter()
+            .enumerate()
+            .map(|(index, spring)| {
+                let point1 = &self.points[spring.p1];
+                let point2 = &self.points[spring.p2];
+
+                let dx = point2.x - point1.x;
+                let dy = point2.y - point1.y;
+                let distance = (dx * dx + dy * dy).sqrt();
+
+                (index, (distance - spring.rest_length).abs() / spring.rest_length)
+            })
+            .max_by(|(_, strain1), (_, strain2)| strain1.total_cmp(strain2));
+
+        match worst {
+            Some((spring, strain)) if strain > self.strain_limit => {
+                Err(SimError::Overstretched { spring, strain })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // step, and if that diverges roll back and retry as two half steps, up to `max_halvings`
+    // deep. On failure the cloth is left at the last good state
+    pub fn step_with_recovery(&mut self, dt: f32, max_halvings: u32) -> Result<StepStats, SimError> {
+        let last_good = self.clone();
+
+        match self.simulate_multithreaded(dt) {
+            Ok(()) => Ok(StepStats { dt, substeps: 1 }),
+            Err(error) => {
+                *self = last_good;
+
+                if max_halvings == 0 {
+                    return Err(error);
                 }
 
-                let (fx, fy) = forces[i][j];
+                let first = self.step_with_recovery(dt / 2.0, max_halvings - 1)?;
+                let second = self.step_with_recovery(dt / 2.0, max_halvings - 1)?;
+
+                Ok(first.merge(second))
+            }
+        }
+    }
+
+    // largest dt the explicit integrator should be trusted with right now: the stiffest spring
+    // has to be resolved (dt < 2 / omega, omega = sqrt(k / m)) and no point may cross more than
+    // a fraction of the shortest spring in one step
+    pub fn stable_dt(&self) -> f32 {
+        let (max_k, min_rest) = self.springs
+            .iter()
+            .fold((0.0f32, f32::MAX), |(max_k, min_rest), spring| {
+                (max_k.max(spring.max_stiffness()), min_rest.min(spring.rest_length))
+            });
+        let max_k = self.active_fem().map_or(max_k, Fem::max_stiffness);
+
+        // the lightest free point oscillates fastest
+        let min_mass = self.points
+            .iter()
+            .filter(|point| !point.fixed)
+            .map(|point| point.mass)
+            .fold(f32::MAX, f32::min);
+
+        let spring_dt = if max_k > 0.0 { 2.0 * (min_mass / max_k).sqrt() } else { f32::MAX };
+
+        let max_speed = self.points
+            .iter()
+            .map(|point| (point.vx * point.vx + point.vy * point.vy).sqrt())
+            .fold(0.0f32, f32::max);
+        let velocity_dt = if max_speed > 0.0 { min_rest / max_speed } else { f32::MAX };
+
+        (DT_SAFETY * spring_dt.min(velocity_dt)).min(MAX_DT)
+    }
+
+    // advance by `frame_time` in equal substeps no larger than `stable_dt`, capped at
+    // `max_substeps`. On failure the cloth is rolled back to where the frame started
+    pub fn advance(&mut self, frame_time: f32, max_substeps: usize) -> Result<StepStats, SimError> {
+        let substeps = ((frame_time / self.stable_dt()).ceil() as usize).clamp(1, max_substeps);
+        let dt = frame_time / (substeps as f32);
 
-                //accelaration
-                point.ax = fx / self.m;
-                point.ay = fy / self.m;
+        let last_good = self.clone();
+        for _ in 0..substeps {
+            if let Err(error) = self.simulate_multithreaded(dt) {
+                *self = last_good;
+                return Err(error);
+            }
+        }
+
+        Ok(StepStats { dt, substeps })
+    }
+
+    pub fn simulate(&mut self, dt: f32) -> Result<(), SimError> {
+        let environment = self.environment();
+        let field_forces = self.field_forces();
+        let mut forces = Vec::with_capacity(self.points.len());
+
+        for (index, point) in self.points.iter().enumerate() {
+            let mut total_force_x = 0.0;
+            let mut total_force_y = 0.0;
 
-                let prev_x = point.x;
-                let prev_y = point.y;
-                point.x += point.vx * dt + 0.5 * point.ax * dt * dt;
-                point.y += point.vy * dt + 0.5 * point.ay * dt * dt;
+            //spring and damper
+            for spring in self.active_springs() {
+                if spring.p1 == index || spring.p2 == index {
+                    let point1 = &self.points[spring.p1];
+                    let point2 = &self.points[spring.p2];
 
-                //floor colision
-                if point.y < -32.0 {
-                    point.y = -32.0;
-                    point.vy = 0.0;
+                    let (fx, fy) = spring.force(point1, point2, spring.p1 == index, self.damping);
+                    total_force_x += fx;
+                    total_force_y += fy;
                 }
+            }
+
+            //gravity and air drag
+            let (environment_force_x, environment_force_y) = environment.force(point);
+
+            //wind and pressure
+            let (field_force_x, field_force_y) = field_forces[index];
+
+            //external forces
+            let mut rng = rand::thread_rng();
+            let ext_force_x = rng.gen_range(-1.0..1.0) * point.ext_m;
+            let ext_force_y = rng.gen_range(-1.0..1.0) * point.ext_m;
+
+            //total
+            total_force_x += environment_force_x + field_force_x + ext_force_x;
+            total_force_y += environment_force_y + field_force_y + ext_force_y;
 
-                //velocity
-                let new_vx = (point.x - prev_x) / dt;
-                let new_vy = (point.y - prev_y) / dt;
-                point.vx = if point.y == -32.0 { -new_vy } else { new_vx };
-                point.vy = if point.y == -32.0 { -new_vy } else { new_vy };
+            forces.push((total_force_x, total_force_y));
+        }
+
+        for (point, (fx, fy)) in self.points.iter_mut().zip(forces) {
+            if point.fixed {
+                continue;
+            }
+
+            //accelaration
+            point.ax = fx / point.mass;
+            point.ay = fy / point.mass;
+
+            let prev_x = point.x;
+            let prev_y = point.y;
+            point.x += point.vx * dt + 0.5 * point.ax * dt * dt;
+            point.y += point.vy * dt + 0.5 * point.ay * dt * dt;
+
+            //floor colision
+            if point.y < FLOOR_Y {
+                point.y = FLOOR_Y;
+                point.vy = 0.0;
             }
+
+            //velocity
+            let new_vx = (point.x - prev_x) / dt;
+            let new_vy = (point.y - prev_y) / dt;
+            point.vx = if point.y == FLOOR_Y { -new_vy } else { new_vx };
+            point.vy = if point.y == FLOOR_Y { -new_vy } else { new_vy };
         }
+
+        self.apply_constraints(dt);
+
+        self.check_stability()
     }
 
-    pub fn simulate_multithreaded(&mut self, dt: f32) {
+    pub fn simulate_multithreaded(&mut self, dt: f32) -> Result<(), SimError> {
         let num_threads = 4 * (*CORE_COUNT as u32);
-        let points_per_thread = (self.points.len() * self.points[0].len()) / (num_threads as usize);
+        let points_per_thread = (self.points.len() / (num_threads as usize)).max(1);
 
-        let points_read_flattened = flatten_points(&self.points);
+        let points_read = self.points.clone();
+        let environment = self.environment();
+        let field_forces = self.field_forces();
+        let springs: &[Spring] = if self.active_fem().is_some() { &[] } else { &self.springs };
 
-        let num_cols = self.points[0].len();
+        // every spring's force once up front, then they're added up per point
+        let point_arrays = PointArrays::from(points_read.as_slice());
+        let spring_arrays = SpringArrays::from(springs);
+        let relative_damping = environment.damping == Damping::Relative;
+        let mut spring_forces = vec![(0.0, 0.0); springs.len()];
+        spring_forces
+            .par_chunks_mut(SPRING_CHUNK)
+            .enumerate()
+            .for_each(|(chunk, forces)| {
+                spring_kernel::spring_forces(
+                    &point_arrays,
+                    &spring_arrays,
+                    relative_damping,
+                    chunk * SPRING_CHUNK,
+                    forces
+                )
+            });
 
-        let mut points_write_flattened = flatten_points(&self.points.clone());
+        let spring_totals = match self.spring_parallelism {
+            SpringParallelism::Gather => {
+                spring_kernel::gather(&point_arrays, &spring_arrays, &spring_forces, relative_damping)
+            }
+            SpringParallelism::Coloured => {
+                if !self.spring_colouring.matches(&spring_arrays, points_read.len()) {
+                    self.spring_colouring = Colouring::new(&spring_arrays, points_read.len());
+                }
+                spring_kernel::scatter(
+                    &point_arrays,
+                    &spring_arrays,
+                    &spring_forces,
+                    &self.spring_colouring.colours,
+                    relative_damping
+                )
+            }
+        };
 
-        points_write_flattened
+        self.points
             .par_chunks_mut(points_per_thread)
-            .for_each(|write_chunk| {
-                simulate_chunk(
-                    &points_read_flattened,
-                    write_chunk,
-                    &self.springs,
-                    dt,
-                    self.g,
-                    self.m,
-                    self.g_on,
-                    num_cols
-                )
+            .zip(field_forces.par_chunks(points_per_thread))
+            .zip(spring_totals.par_chunks(points_per_thread))
+            .for_each(|((write_chunk, field_chunk), spring_chunk)| {
+                simulate_chunk(write_chunk, field_chunk, spring_chunk, dt, environment)
             });
 
-        // Update the original points with the modified values
-        self.points = unflatten_points(&points_write_flattened, num_cols);
+        self.apply_constraints(dt);
+
+        self.check_stability()
     }
-}
 
-pub fn simulate_chunk(
-    points_read: &[Point],
-    points_write: &mut [Point],
-    springs: &[Spring],
-    dt: f32,
-    g: f32,
-    m: f32,
-    g_on: bool,
-    num_cols: usize
-) {
-    for point in points_write.iter_mut() {
-        if point.fixed {
-            continue;
+    // position corrections made after the points have moved, rest lengths that gave way and
+    // heat spreading out
+    fn apply_constraints(&mut self, dt: f32) {
+        self.conduct_heat(dt);
+
+        if self.inextensible {
+            self.enforce_lengths(dt);
         }
 
-        let mut total_force_x = 0.0;
-        let mut total_force_y = 0.0;
+        // as in `active_springs`, springs that are only drawn under `ForceModel::Fem` don't
+        // yield either. The elements have no plasticity, so such a cloth keeps its rest shape
+        let yielding = if self.active_fem().is_some() { &mut [][..] } else { &mut self.springs[..] };
+        for spring in yielding.iter_mut().filter(|spring| spring.plastic_flow > 0.0) {
+            let point1 = &self.points[spring.p1];
+            let point2 = &self.points[spring.p2];
+            let distance = ((point2.x - point1.x).powi(2) + (point2.y - point1.y).powi(2)).sqrt();
 
-        for spring in springs {
-            let row1 = spring.p1.0;
-            let col1 = spring.p1.1;
+            // thermal expansion isn't strain, measure against the heated length
+            spring.yield_to(distance / spring.thermal_scale(point1, point2), dt);
+        }
 
-            let row2 = spring.p2.0;
-            let col2 = spring.p2.1;
+        if let Some(shape_matching) = &self.shape_matching {
+            shape_matching.apply(&mut self.points, dt);
+            for point in &mut self.points {
+                point.y = point.y.max(FLOOR_Y);
+            }
+        }
+    }
 
-            let p1 = &points_read[row1 * num_cols + col1];
-            // println!("Point 1: {}", row1 * num_cols + col1);
+    // heat flows along every spring in proportion to the temperature difference across it,
+    // then each point loses a little to the surrounding air. Fluxes are all worked out from the
+    // temperatures at the start of the step, so the order of the springs doesn't matter
+    fn conduct_heat(&mut self, dt: f32) {
+        if self.points.iter().all(|point| point.temperature == 0.0) {
+            return;
+        }
 
-            let p2 = &points_read[row2 * num_cols + col2];
-            // println!("Point 2: {}", row2 * num_cols + col2);
+        let mut change = vec![0.0; self.points.len()];
+        for spring in self.springs.iter().filter(|spring| !spring.bending) {
+            let flux = self.conductivity * (self.points[spring.p2].temperature - self.points[spring.p1].temperature) * dt;
+            change[spring.p1] += flux;
+            change[spring.p2] -= flux;
+        }
 
-            // Apply forces only if the current point is one of the points connected by the spring
-            if point == p1 || point == p2 {
-                let dx = p2.x - p1.x;
-                let dy = p2.y - p1.y;
+        for (point, change) in self.points.iter_mut().zip(change) {
+            point.temperature += change - self.cooling * point.temperature * dt;
+        }
+    }
 
-                let dist = (dx * dx + dy * dy).sqrt();
-                let magnitude = spring.spring_coeff * (dist - spring.rest_length);
+    // raise the temperature of every point within `radius` of (x, y) by up to `rate` degrees a
+    // second, fading out towards the edge
+    pub fn heat(&mut self, x: f32, y: f32, radius: f32, rate: f32, dt: f32) {
+        for point in &mut self.points {
+            let distance = ((point.x - x).powi(2) + (point.y - y).powi(2)).sqrt();
+            if distance < radius {
+                point.temperature += rate * (1.0 - distance / radius) * dt;
+            }
+        }
+    }
 
-                let spring_force_x = (magnitude * dx) / dist;
-                let spring_force_y = (magnitude * dy) / dist;
+    // per point temperature
+    pub fn temperatures(&self) -> Vec<f32> {
+        self.points
+            .iter()
+            .map(|point| point.temperature)
+            .collect()
+    }
 
-                let damping_force_x = -point.vx * spring.damp_coeff;
-                let damping_force_y = -point.vy * spring.damp_coeff;
+    // pull the ends of every stretched or squashed spring back to its rest length, the lighter
+    // end moving further, then fold the correction into the velocities so the next step doesn't
+    // undo it. Sweeping the springs in order only converges gradually along a long chain, hence
+    // the repeated passes
+    fn enforce_lengths(&mut self, dt: f32) {
+        let before = self.positions();
 
-                if point == p1 {
-                    total_force_x += spring_force_x + damping_force_x;
-                    total_force_y += spring_force_y + damping_force_y;
-                } else {
-                    total_force_x -= spring_force_x - damping_force_x;
-                    total_force_y -= spring_force_y - damping_force_y;
+        for _ in 0..LENGTH_ITERATIONS {
+            for spring in self.springs.iter().filter(|spring| !spring.bending) {
+                let point1 = &self.points[spring.p1];
+                let point2 = &self.points[spring.p2];
+
+                let inv_mass1 = if point1.fixed { 0.0 } else { 1.0 / point1.mass };
+                let inv_mass2 = if point2.fixed { 0.0 } else { 1.0 / point2.mass };
+                let inv_mass_sum = inv_mass1 + inv_mass2;
+
+                let dx = point2.x - point1.x;
+                let dy = point2.y - point1.y;
+                let distance = (dx * dx + dy * dy).sqrt();
+                if inv_mass_sum == 0.0 || distance == 0.0 {
+                    continue;
                 }
+
+                let correction = (distance - spring.rest_length) / (distance * inv_mass_sum);
+
+                let point1 = &mut self.points[spring.p1];
+                point1.x += dx * correction * inv_mass1;
+                point1.y += dy * correction * inv_mass1;
+
+                let point2 = &mut self.points[spring.p2];
+                point2.x -= dx * correction * inv_mass2;
+                point2.y -= dy * correction * inv_mass2;
             }
         }
 
-        // gravity
-        let gravity_force_x = 0.0;
-        let gravity_force_y = if g_on { -g * m } else { 0.0 };
+        for (point, [x, y]) in self.points.iter_mut().zip(before) {
+            point.y = point.y.max(FLOOR_Y);
+            point.vx += (point.x - x) / dt;
+            point.vy += (point.y - y) / dt;
+        }
+    }
+}
+
+impl From<&[Point]> for PointArrays {
+    fn from(points: &[Point]) -> Self {
+        PointArrays {
+            x: points.iter().map(|point| point.x).collect(),
+            y: points.iter().map(|point| point.y).collect(),
+            vx: points.iter().map(|point| point.vx).collect(),
+            vy: points.iter().map(|point| point.vy).collect(),
+            temperature: points.iter().map(|point| point.temperature).collect(),
+        }
+    }
+}
+
+impl From<&[Spring]> for SpringArrays {
+    fn from(springs: &[Spring]) -> Self {
+        SpringArrays {
+            p1: springs.iter().map(|spring| spring.p1).collect(),
+            p2: springs.iter().map(|spring| spring.p2).collect(),
+            rest_length: springs.iter().map(|spring| spring.rest_length).collect(),
+            spring_coeff: springs.iter().map(|spring| spring.spring_coeff).collect(),
+            damp_coeff: springs.iter().map(|spring| spring.damp_coeff).collect(),
+            stiffening_strain: springs.iter().map(|spring| spring.stiffening_strain).collect(),
+            stiffening_ratio: springs.iter().map(|spring| spring.stiffening_ratio).collect(),
+            thermal_expansion: springs.iter().map(|spring| spring.thermal_expansion).collect(),
+        }
+    }
+}
+
+// `spring_forces` are the total spring force on each point
+pub fn simulate_chunk(
+    points_write: &mut [Point],
+    field_forces: &[(f32, f32)],
+    spring_forces: &[(f32, f32)],
+    dt: f32,
+    environment: Environment
+) {
+    for ((point, &(field_force_x, field_force_y)), &(spring_force_x, spring_force_y)) in points_write
+        .iter_mut()
+        .zip(field_forces)
+        .zip(spring_forces) {
+        if point.fixed {
+            continue;
+        }
+
+        // gravity and air drag
+        let (environment_force_x, environment_force_y) = environment.force(point);
 
         // external forces
         let mut rng = rand::thread_rng();
@@ -266,12 +1122,12 @@ pub fn simulate_chunk(
         let ext_force_y = rng.gen_range(-1.0..1.0) * point.ext_m;
 
         // total
-        total_force_x += gravity_force_x + ext_force_x;
-        total_force_y += gravity_force_y + ext_force_y;
+        let total_force_x = spring_force_x + environment_force_x + field_force_x + ext_force_x;
+        let total_force_y = spring_force_y + environment_force_y + field_force_y + ext_force_y;
 
         // acceleration
-        point.ax = total_force_x / m;
-        point.ay = total_force_y / m;
+        point.ax = total_force_x / point.mass;
+        point.ay = total_force_y / point.mass;
 
         let prev_x = point.x;
         let prev_y = point.y;
@@ -280,29 +1136,250 @@ pub fn simulate_chunk(
         point.y += point.vy * dt + 0.5 * point.ay * dt * dt;
 
         // floor collision
-        if point.y < -32.0 {
-            point.y = -32.0;
+        if point.y < FLOOR_Y {
+            point.y = FLOOR_Y;
             point.vy = 0.0;
         }
 
         // velocity
         let new_vx = (point.x - prev_x) / dt;
         let new_vy = (point.y - prev_y) / dt;
-        point.vx = if point.y == -32.0 { -new_vy } else { new_vx };
-        point.vy = if point.y == -32.0 { -new_vy } else { new_vy };
+        point.vx = if point.y == FLOOR_Y { -new_vy } else { new_vx };
+        point.vy = if point.y == FLOOR_Y { -new_vy } else { new_vy };
     }
 }
 
-fn flatten_points(points: &[Vec<Point>]) -> Vec<Point> {
-    points
-        .iter()
-        .flat_map(|row| row.iter().cloned())
-        .collect()
+// the first of `indices` that isn't a point, as an error naming `what` holds it
+fn check_indices(what: &str, indices: impl IntoIterator<Item = usize>, num_points: usize) -> Result<(), String> {
+    match indices.into_iter().find(|&index| index >= num_points) {
+        Some(index) => Err(format!("{} refers to point {} of {}", what, index, num_points)),
+        None => Ok(()),
+    }
 }
 
-fn unflatten_points(flattened_points: &[Point], row_len: usize) -> Vec<Vec<Point>> {
-    flattened_points
-        .chunks(row_len)
-        .map(|chunk| chunk.to_vec())
-        .collect()
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+// binary snapshots start with these bytes and then `SNAPSHOT_VERSION`. bincode keeps no field
+// names, so `#[serde(default)]` can't fill in a field an older file lacks and any change to a
+// saved struct breaks old files. Bump the version whenever that happens so they're turned away
+// with a clear error. JSON carries names and keeps loading across changes, so keep fixtures as
+// JSON
+const SNAPSHOT_MAGIC: &[u8; 4] = b"CLTH";
+const SNAPSHOT_VERSION: u32 = 2;
+
+// JSON for a .json path and versioned bincode otherwise, for cloths and whole worlds alike
+pub(crate) fn write_snapshot<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let bytes = if is_json(path) {
+        serde_json::to_vec_pretty(value).map_err(invalid_data)?
+    } else {
+        let mut bytes = SNAPSHOT_MAGIC.to_vec();
+        bytes.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        bincode::serialize_into(&mut bytes, value).map_err(invalid_data)?;
+        bytes
+    };
+
+    fs::write(path, bytes)
+}
+
+pub(crate) fn read_snapshot<T: DeserializeOwned>(path: &Path) -> io::Result<T> {
+    let bytes = fs::read(path)?;
+
+    if is_json(path) {
+        return serde_json::from_slice(&bytes).map_err(invalid_data);
+    }
+
+    let Some(body) = bytes.strip_prefix(SNAPSHOT_MAGIC) else {
+        return Err(invalid_data("not a binary snapshot, or one saved before they were versioned"));
+    };
+    let version = body.get(..4).map(|version| u32::from_le_bytes(version.try_into().unwrap()));
+    if version != Some(SNAPSHOT_VERSION) {
+        return Err(
+            invalid_data(
+                format!(
+                    "binary snapshot is format version {:?}, this build reads {}. Save it as .json from the build that made it",
+                    version,
+                    SNAPSHOT_VERSION
+                )
+            )
+        );
+    }
+
+    bincode::deserialize(&body[4..]).map_err(invalid_data)
+}
+
+pub(crate) fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("cloth_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn binary_snapshots_check_their_version() {
+        let path = temp_path("snapshot.bin");
+        let cloth = Cloth::new(4, 3);
+        cloth.save(&path).unwrap();
+        assert_eq!(Cloth::load(&path).unwrap().positions(), cloth.positions());
+
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[4..8].copy_from_slice(&(SNAPSHOT_VERSION + 1).to_le_bytes());
+        fs::write(&path, bytes).unwrap();
+        let error = Cloth::load(&path).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn out_of_range_indices_are_rejected_on_load() {
+        let path = temp_path("bad_spring.json");
+        let mut cloth = Cloth::new(4, 3);
+        cloth.springs[0].p2 = cloth.points.len();
+        cloth.save(&path).unwrap();
+
+        let error = Cloth::load(&path).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn springs_to_themselves_are_rejected_on_load() {
+        let path = temp_path("looped_spring.json");
+        let mut cloth = Cloth::new(4, 3);
+        cloth.springs[0].p2 = cloth.springs[0].p1;
+        cloth.save(&path).unwrap();
+
+        let error = Cloth::load(&path).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn coloured_scatter_matches_gather() {
+        let cloth = Cloth::new(12, 9);
+        let points = PointArrays::from(cloth.points.as_slice());
+        let springs = SpringArrays::from(cloth.springs.as_slice());
+        let mut forces = vec![(0.0, 0.0); cloth.springs.len()];
+        spring_kernel::spring_forces(&points, &springs, false, 0, &mut forces);
+
+        let colouring = Colouring::new(&springs, cloth.points.len());
+        let scattered = spring_kernel::scatter(&points, &springs, &forces, &colouring.colours, false);
+        let gathered = spring_kernel::gather(&points, &springs, &forces, false);
+        for (a, b) in scattered.iter().zip(&gathered) {
+            assert!((a.0 - b.0).abs() < 1e-4 && (a.1 - b.1).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn colouring_follows_rewired_springs() {
+        let mut cloth = Cloth::new(6, 6);
+        cloth.spring_parallelism = SpringParallelism::Coloured;
+        cloth.simulate_multithreaded(0.001).unwrap();
+
+        // same number of springs, different ends
+        let last = cloth.points.len() - 1;
+        cloth.springs[0].p2 = last;
+        cloth.simulate_multithreaded(0.001).unwrap();
+
+        let springs = SpringArrays::from(cloth.springs.as_slice());
+        assert!(cloth.spring_colouring.matches(&springs, cloth.points.len()));
+    }
+
+    #[test]
+    fn default_cloth_hangs_without_tearing() {
+        let mut cloth = Cloth::new(20, 20);
+        for _ in 0..600 {
+            cloth.simulate_multithreaded(0.01).unwrap();
+            assert!(cloth.diagnostics().max_strain < 1.0);
+        }
+    }
+
+    #[test]
+    fn springs_only_yield_while_they_hold_the_cloth() {
+        let mut cloth = Cloth::with_material(6, 6, &Material::FOIL);
+        cloth.force_model = ForceModel::Fem;
+        for point in &mut cloth.points {
+            point.x *= 1.5;
+        }
+        let rest_lengths: Vec<f32> = cloth.springs.iter().map(|spring| spring.rest_length).collect();
+
+        cloth.apply_constraints(0.01);
+        assert!(cloth.springs.iter().zip(&rest_lengths).all(|(spring, &rest)| spring.rest_length == rest));
+
+        cloth.force_model = ForceModel::Springs;
+        cloth.apply_constraints(0.01);
+        assert!(cloth.springs.iter().zip(&rest_lengths).any(|(spring, &rest)| spring.rest_length > rest));
+    }
+
+    #[test]
+    fn a_step_into_nan_is_rolled_back() {
+        let mut cloth = Cloth::new(5, 5);
+        let free = cloth.points.iter().position(|point| !point.fixed).unwrap();
+        cloth.points[free].vx = f32::NAN;
+        let positions = cloth.positions();
+
+        let error = cloth.step_with_recovery(0.01, 2).err().unwrap();
+        assert!(matches!(error, SimError::NonFinite { .. }));
+        assert_eq!(cloth.positions(), positions);
+    }
+
+    #[test]
+    fn stable_dt_shrinks_as_springs_stiffen() {
+        let mut cloth = Cloth::new(5, 5);
+        let mut dts = Vec::new();
+        for stiffness in [1.0, 100.0, 1e4, 1e6] {
+            for spring in &mut cloth.springs {
+                spring.spring_coeff = stiffness;
+            }
+            dts.push(cloth.stable_dt());
+        }
+
+        assert!(dts.iter().all(|&dt| dt > 0.0 && dt <= MAX_DT));
+        assert!(dts.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(dts[3] < dts[0]);
+    }
+
+    #[test]
+    fn rest_length_only_creeps_past_yield() {
+        let mut spring = Spring::new(0, 1, 1.0);
+        Material::FOIL.apply(&mut spring);
+
+        spring.yield_to(1.01, 0.01);
+        assert_eq!(spring.rest_length, 1.0);
+        assert_eq!(spring.plastic_strain, 0.0);
+
+        // part of the way towards the yield surface each step, never past it
+        spring.yield_to(1.5, 0.01);
+        assert!(spring.rest_length > 1.0 && spring.rest_length < 1.5 / (1.0 + spring.yield_strain));
+        assert!(spring.plastic_strain > 0.0);
+
+        let stretched = spring.rest_length;
+        spring.yield_to(0.5, 0.01);
+        assert!(spring.rest_length < stretched);
+    }
+
+    #[test]
+    fn conduction_moves_heat_without_losing_any() {
+        let mut cloth = Cloth::new(6, 6);
+        cloth.cooling = 0.0;
+        cloth.points[14].temperature = 100.0;
+
+        for _ in 0..50 {
+            cloth.conduct_heat(0.01);
+        }
+
+        let total: f32 = cloth.points.iter().map(|point| point.temperature).sum();
+        assert!((total - 100.0).abs() < 1e-3);
+        assert!(cloth.points[14].temperature < 100.0);
+        assert!(cloth.points[15].temperature > 0.0);
+    }
 }
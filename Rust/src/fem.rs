@@ -0,0 +1,217 @@
+use rayon::prelude::*;
+use serde::{ Deserialize, Serialize };
+
+use crate::cloth::Point;
+use crate::matrix::{ determinant, multiply, polar_rotation, symmetric, transpose, Matrix };
+
+// which forces hold the cloth together
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ForceModel {
+    #[default]
+    Springs,
+    // the cloth's `Fem` elements, the springs are then only drawn
+    Fem,
+}
+
+// one triangle of the membrane, remembering its rest shape
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Element {
+    pub indices: [usize; 3],
+    // inverse of the rest edge matrix [x1 - x0, x2 - x0], row major
+    pub rest_inverse: [[f32; 2]; 2],
+    pub rest_area: f32,
+}
+
+// the cloth as a continuous membrane of linear elastic triangles (plane stress), described by
+// measurable constants instead of spring stiffnesses. Co-rotational: each triangle's rotation
+// is taken out before measuring strain, so spinning or swinging a piece of cloth doesn't
+// register as stretch the way it would with plain linear elements
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fem {
+    pub elements: Vec<Element>,
+    // in newtons per metre, the thickness is folded in since the cloth is 2D
+    pub youngs_modulus: f32,
+    pub poisson_ratio: f32,
+    // resists the rate of strain, like the dampers on the springs
+    pub viscosity: f32,
+}
+
+pub const DEFAULT_YOUNGS_MODULUS: f32 = 10.0;
+pub const DEFAULT_POISSON_RATIO: f32 = 0.3;
+pub const DEFAULT_VISCOSITY: f32 = 0.05;
+
+impl Fem {
+    // elements at rest in `rest_positions`, triangles with no area are left out
+    pub fn new(
+        rest_positions: &[[f32; 2]],
+        triangles: &[[usize; 3]],
+        youngs_modulus: f32,
+        poisson_ratio: f32,
+        viscosity: f32
+    ) -> Self {
+        let elements = triangles
+            .iter()
+            .filter_map(|&indices| {
+                let [a, b, c] = indices.map(|index| rest_positions[index]);
+                let edges = [
+                    [b[0] - a[0], c[0] - a[0]],
+                    [b[1] - a[1], c[1] - a[1]],
+                ];
+
+                let det = determinant(&edges);
+                if det.abs() < f32::EPSILON {
+                    return None;
+                }
+
+                let rest_inverse = [
+                    [edges[1][1] / det, -edges[0][1] / det],
+                    [-edges[1][0] / det, edges[0][0] / det],
+                ];
+
+                Some(Element { indices, rest_inverse, rest_area: det.abs() / 2.0 })
+            })
+            .collect();
+
+        Fem { elements, youngs_modulus, poisson_ratio, viscosity }
+    }
+
+    // Lamé parameters for plane stress
+    fn lame(&self) -> (f32, f32) {
+        let (e, nu) = (self.youngs_modulus, self.poisson_ratio);
+        let lambda = (e * nu) / (1.0 - nu * nu);
+        let mu = e / (2.0 * (1.0 + nu));
+        (lambda, mu)
+    }
+
+    // rough stiffness of one element for `Cloth::stable_dt`, the membrane's p-wave modulus
+    pub fn max_stiffness(&self) -> f32 {
+        let (lambda, mu) = self.lame();
+        lambda + 2.0 * mu
+    }
+
+    // force on every point, summed over the elements around it
+    pub fn forces(&self, points: &[Point]) -> Vec<(f32, f32)> {
+        let element_forces: Vec<[(f32, f32); 3]> = self.elements
+            .par_iter()
+            .map(|element| self.element_forces(element, points))
+            .collect();
+
+        let mut forces = vec![(0.0, 0.0); points.len()];
+        for (element, corner_forces) in self.elements.iter().zip(element_forces) {
+            for (&index, (fx, fy)) in element.indices.iter().zip(corner_forces) {
+                forces[index].0 += fx;
+                forces[index].1 += fy;
+            }
+        }
+
+        forces
+    }
+
+    // stored elastic energy, area times mu e:e + lambda / 2 tr(e)^2 per element
+    pub fn energy(&self, points: &[Point]) -> f32 {
+        let (lambda, mu) = self.lame();
+
+        self.elements
+            .iter()
+            .map(|element| {
+                let (_, strain) = corotated_strain(element, points);
+                let trace = strain[0][0] + strain[1][1];
+                let contraction = strain[0][0] * strain[0][0] + 2.0 * strain[0][1] * strain[0][1] + strain[1][1] * strain[1][1];
+                element.rest_area * (mu * contraction + 0.5 * lambda * trace * trace)
+            })
+            .sum()
+    }
+
+    // f = -area P Dm^-T, with the first Piola-Kirchhoff stress P = R sigma. The first two columns
+    // are the forces on corners 1 and 2, corner 0 takes the rest so the element exerts no net
+    // force
+    fn element_forces(&self, element: &Element, points: &[Point]) -> [(f32, f32); 3] {
+        let (lambda, mu) = self.lame();
+        let (rotation, strain) = corotated_strain(element, points);
+
+        // strain rate, measured in the same rotated frame
+        let [a, b, c] = element.indices.map(|index| &points[index]);
+        let velocities = [
+            [b.vx - a.vx, c.vx - a.vx],
+            [b.vy - a.vy, c.vy - a.vy],
+        ];
+        let strain_rate = symmetric(&multiply(&transpose(&rotation), &multiply(&velocities, &element.rest_inverse)));
+
+        let trace = strain[0][0] + strain[1][1];
+        let mut stress = [[0.0; 2]; 2];
+        for i in 0..2 {
+            for j in 0..2 {
+                let diagonal = if i == j { lambda * trace } else { 0.0 };
+                stress[i][j] = diagonal + 2.0 * mu * strain[i][j] + self.viscosity * strain_rate[i][j];
+            }
+        }
+
+        let piola = multiply(&rotation, &stress);
+        let h = multiply(&piola, &transpose(&element.rest_inverse));
+
+        let f1 = (-element.rest_area * h[0][0], -element.rest_area * h[1][0]);
+        let f2 = (-element.rest_area * h[0][1], -element.rest_area * h[1][1]);
+        [(-f1.0 - f2.0, -f1.1 - f2.1), f1, f2]
+    }
+}
+
+// rotation R out of the deformation gradient F and the small strain sym(R^T F) - I left
+// after taking it out
+fn corotated_strain(element: &Element, points: &[Point]) -> (Matrix, Matrix) {
+    let [a, b, c] = element.indices.map(|index| &points[index]);
+    let edges = [
+        [b.x - a.x, c.x - a.x],
+        [b.y - a.y, c.y - a.y],
+    ];
+    let deformation = multiply(&edges, &element.rest_inverse);
+
+    let rotation = polar_rotation(&deformation);
+
+    let mut strain = symmetric(&multiply(&transpose(&rotation), &deformation));
+    strain[0][0] -= 1.0;
+    strain[1][1] -= 1.0;
+
+    (rotation, strain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cloth::Cloth;
+    use crate::material::Material;
+    use crate::mesh::Mesh;
+
+    fn rotated(points: &[Point], angle: f32) -> Vec<Point> {
+        let (sin, cos) = angle.sin_cos();
+        points
+            .iter()
+            .map(|point| Point { x: cos * point.x - sin * point.y, y: sin * point.x + cos * point.y, ..*point })
+            .collect()
+    }
+
+    #[test]
+    fn corotated_forces_ignore_rigid_rotation() {
+        let cloth = Cloth::from_mesh(&Mesh::triangle_lattice(4, 4, 1.0), &Material::default());
+        let fem = cloth.fem.as_ref().unwrap();
+
+        // no force at rest, however the rest shape is turned
+        for angle in [0.0, 0.7, 2.5] {
+            for (fx, fy) in fem.forces(&rotated(&cloth.points, angle)) {
+                assert!(fx.abs() < 1e-4 && fy.abs() < 1e-4);
+            }
+        }
+
+        // stretched, the forces turn with the cloth and keep their size
+        let mut stretched = cloth.points.clone();
+        for point in &mut stretched {
+            point.x *= 1.2;
+        }
+        let angle = 0.7f32;
+        let (sin, cos) = angle.sin_cos();
+        let forces = fem.forces(&stretched);
+        for ((fx, fy), (rx, ry)) in forces.iter().zip(fem.forces(&rotated(&stretched, angle))) {
+            assert!((cos * fx - sin * fy - rx).abs() < 1e-3 && (sin * fx + cos * fy - ry).abs() < 1e-3);
+        }
+        assert!(forces.iter().any(|&(fx, _)| fx.abs() > 1e-2));
+    }
+}
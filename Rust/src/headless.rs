@@ -0,0 +1,126 @@
+use std::fs::{ self, File };
+use std::io::{ BufWriter, Write };
+use std::path::Path;
+use std::time::Instant;
+
+use crate::offscreen::{ self, OffscreenRenderer };
+use crate::recorder::Recorder;
+use crate::simulation::RunStats;
+use crate::{ arg_value, initial_world };
+
+// run the simulation without a window, e.g. `--headless --steps 2000 --record out --every 4`
+//   --recover            retry diverging steps with a smaller dt
+//   --adaptive           substep each dt at the cloth's stable timestep
+//   --diagnostics path   per-step energy, momentum and dt as CSV
+//   --frames dir         numbered PNGs (ffmpeg -i dir/frame_%05d.png out.mp4)
+pub fn run(args: &[String]) {
+    let steps: usize = arg_value(args, "--steps").map_or(1000, |s| s.parse().expect("invalid --steps"));
+    let dt: f32 = arg_value(args, "--dt").map_or(0.01, |s| s.parse().expect("invalid --dt"));
+    let recover = args.iter().any(|arg| arg == "--recover");
+    let adaptive = args.iter().any(|arg| arg == "--adaptive");
+    let every: usize = arg_value(args, "--every").map_or(4, |s| s.parse().expect("invalid --every"));
+
+    let mut world = initial_world(args);
+
+    let record_dir = arg_value(args, "--record");
+    let mut recorder = record_dir.map(|_| Recorder::new(every, dt));
+    if let Some(recorder) = &mut recorder {
+        recorder.capture(&world);
+    }
+
+    let frames_dir = arg_value(args, "--frames");
+    let renderer = frames_dir.map(|dir| {
+        let (width, height) = arg_value(args, "--size").map_or((1280, 1000), |s|
+            offscreen::parse_size(s).expect("invalid --size, expected WIDTHxHEIGHT")
+        );
+        fs::create_dir_all(dir).expect("Failed to create frames directory");
+        OffscreenRenderer::new(width, height).expect("Failed to create offscreen context")
+    });
+    let mut frame = 0;
+
+    // one row of energy / momentum per step
+    let mut diagnostics_log = arg_value(args, "--diagnostics").map(|path| {
+        let mut file = BufWriter::new(File::create(path).expect("Failed to create diagnostics log"));
+        writeln!(
+            file,
+            "step,time,dt,substeps,kinetic,potential,elastic,total,momentum_x,momentum_y,max_strain"
+        ).expect("Failed to write diagnostics log");
+        file
+    });
+
+    let mut stats = RunStats::default();
+
+    for step in 0..=steps {
+        if let (Some(renderer), Some(dir)) = (&renderer, frames_dir) {
+            if step.is_multiple_of(every) {
+                let path = Path::new(dir).join(format!("frame_{:05}.png", frame));
+                renderer
+                    .write_png(&world, path)
+                    .expect("Failed to write frame");
+                frame += 1;
+            }
+        }
+
+        if step == steps {
+            break;
+        }
+
+        let sim_start = Instant::now();
+        let result = if adaptive {
+            world.advance(dt, 64)
+        } else if recover {
+            world.step_with_recovery(dt, 4)
+        } else {
+            world.simulate_multithreaded(dt)
+        };
+        let sim_end = Instant::now();
+        let sim_time = sim_end.duration_since(sim_start).as_micros();
+        stats.record((sim_time as f32) / 1000.0, result.ok()); // convert to millis
+
+        let step_stats = match result {
+            Ok(step_stats) => step_stats,
+            Err(e) => {
+                println!("Simulation diverged at step {}: {}", step + 1, e);
+                break;
+            }
+        };
+
+        if let Some(file) = &mut diagnostics_log {
+            let d = world.diagnostics();
+            writeln!(
+                file,
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                step + 1,
+                ((step + 1) as f32) * dt,
+                step_stats.dt,
+                step_stats.substeps,
+                d.kinetic,
+                d.potential,
+                d.elastic,
+                d.total_energy(),
+                d.momentum.0,
+                d.momentum.1,
+                d.max_strain
+            ).expect("Failed to write diagnostics log");
+        }
+
+        if let Some(recorder) = &mut recorder {
+            recorder.capture(&world);
+        }
+    }
+
+    if let Some(mut file) = diagnostics_log {
+        file.flush().expect("Failed to write diagnostics log");
+    }
+
+    stats.print_summary();
+
+    if let Some(dir) = frames_dir {
+        println!("Rendered {} frames to {}", frame, dir);
+    }
+
+    if let (Some(recorder), Some(dir)) = (recorder, record_dir) {
+        recorder.export(dir).expect("Failed to export recording");
+        println!("Exported {} frames to {}", recorder.num_frames(), dir);
+    }
+}
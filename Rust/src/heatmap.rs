@@ -0,0 +1,57 @@
+use crate::cloth::Cloth;
+use crate::world::World;
+
+// per-point quantity the springs can be coloured by instead of plain black
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Heatmap {
+    #[default]
+    Off,
+    Mass,
+    // where springs have yielded, e.g. creases in foil
+    PlasticStrain,
+    Temperature,
+}
+
+impl Heatmap {
+    pub fn next(self) -> Self {
+        match self {
+            Heatmap::Off => Heatmap::Mass,
+            Heatmap::Mass => Heatmap::PlasticStrain,
+            Heatmap::PlasticStrain => Heatmap::Temperature,
+            Heatmap::Temperature => Heatmap::Off,
+        }
+    }
+
+    // one value per point in vertex order, scaled to 0..=1 across every cloth
+    pub fn values(self, world: &World) -> Option<Vec<f32>> {
+        let values: Vec<f32> = match self {
+            Heatmap::Off => {
+                return None;
+            }
+            Heatmap::Mass =>
+                world.cloths
+                    .iter()
+                    .flat_map(|cloth| &cloth.points)
+                    .map(|point| point.mass)
+                    .collect(),
+            Heatmap::PlasticStrain => world.cloths.iter().flat_map(Cloth::plastic_strain).collect(),
+            Heatmap::Temperature => world.cloths.iter().flat_map(Cloth::temperatures).collect(),
+        };
+
+        Some(normalize(values))
+    }
+}
+
+fn normalize(mut values: Vec<f32>) -> Vec<f32> {
+    let (min, max) = values
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), &value| (min.min(value), max.max(value)));
+
+    // a uniform field has nothing to show, leave it all at the cold end
+    let range = max - min;
+    for value in &mut values {
+        *value = if range > 0.0 { (*value - min) / range } else { 0.0 };
+    }
+
+    values
+}
@@ -3,37 +3,256 @@ extern crate glium;
 extern crate winit;
 use std::env;
 
+mod aero;
 mod cloth;
-
-use glium::{ Surface, VertexBuffer };
-use std::{ fs, time::Instant };
-use std::io::Read;
-use cloth::Cloth;
+mod fem;
+mod headless;
+mod heatmap;
+mod material;
+mod matrix;
+mod mesh;
+mod offscreen;
+mod overlay;
+mod pressure;
+mod recorder;
+mod render;
+mod rigid;
+mod rope;
+mod shape_matching;
+mod simulation;
+mod spring_kernel;
+mod timestep;
+mod world;
+
+use glium::Surface;
+use std::time::Instant;
+use std::path::PathBuf;
+use aero::Aerodynamics;
+use cloth::{ Cloth, Damping, SpringParallelism };
+use fem::ForceModel;
+use material::Material;
+use mesh::Mesh;
+use overlay::EnergyGraph;
+use pressure::Blob;
+use rigid::RigidBody;
+use rope::{ Rope, RopeEnds };
+use shape_matching::Jelly;
+use simulation::{ Command, SimulationThread };
+use timestep::FixedTimestep;
+use world::{ World, PALETTE };
 
 extern crate num_cpus;
 use once_cell::sync::Lazy;
 
-pub static CORE_COUNT: Lazy<usize> = Lazy::new(|| num_cpus::get_physical());
+pub static CORE_COUNT: Lazy<usize> = Lazy::new(num_cpus::get_physical);
+
+// value following a `--flag` on the command line
+pub fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(|value| value.as_str())
+}
+
+// move a cloth so it is centred horizontally with its top 30 units up
+pub fn centered(mut cloth: Cloth) -> Cloth {
+    let (min_x, max_x, max_y) = cloth.points
+        .iter()
+        .fold((f32::MAX, f32::MIN, f32::MIN), |(min_x, max_x, max_y), point| {
+            (min_x.min(point.x), max_x.max(point.x), max_y.max(point.y))
+        });
+
+    for point in &mut cloth.points {
+        point.x -= (min_x + max_x) / 2.0;
+        point.y -= max_y;
+        point.y += 30.0; // extra offset
+    }
+
+    cloth
+}
+
+// `--mesh rect|triangle|hex|circle|rope` or `--obj path`, each hung from its top corners
+// apart from the rope, see `rope`
+pub fn mesh_cloth(args: &[String]) -> Cloth {
+    let mesh = match (arg_value(args, "--obj"), arg_value(args, "--mesh")) {
+        (Some(path), _) => Mesh::load_obj(path).expect("Failed to load mesh"),
+        (None, Some("triangle")) => Mesh::triangle_lattice(50, 50, 1.0),
+        (None, Some("hex")) => Mesh::hexagonal(20, 18, 1.0),
+        (None, Some("circle")) => Mesh::circle(20, 1.0),
+        (None, Some("rope")) => {
+            return centered(rope(args));
+        }
+        (None, Some("rect") | None) => Mesh::grid(50, 50, 1.0),
+        (None, Some(other)) => panic!("unknown --mesh {}, try rect, triangle, hex, circle or rope", other),
+    };
+
+    let mut cloth = Cloth::from_mesh(&mesh, &Material::default());
+    cloth.pin_top_corners();
+    centered(cloth)
+}
+
+// `--mesh rope` with `--segments n`, `--pin start|both|none`, `--bending k`, `--slack f`
+// and `--inextensible` for a chain of rigid links
+pub fn rope(args: &[String]) -> Cloth {
+    let defaults = Rope::default();
+    let rope = Rope {
+        segments: arg_value(args, "--segments").map_or(defaults.segments, |s| s.parse().expect("invalid --segments")),
+        ends: arg_value(args, "--pin").map_or(defaults.ends, |s|
+            RopeEnds::parse(s).expect("unknown --pin, try start, both or none")
+        ),
+        bending_stiffness: arg_value(args, "--bending").map_or(defaults.bending_stiffness, |s|
+            s.parse().expect("invalid --bending")
+        ),
+        slack: arg_value(args, "--slack").map_or(defaults.slack, |s| s.parse().expect("invalid --slack")),
+        inextensible: args.iter().any(|arg| arg == "--inextensible"),
+        ..defaults
+    };
+
+    rope.build()
+}
+
+// `count` smaller cloths hung side by side and overlapping, each in its own colour and
+// material so they can be told apart. `count` must be at least one
+pub fn staggered_cloths(count: usize) -> World {
+    let cloths = (0..count)
+        .map(|c| {
+            let mut cloth = centered(Cloth::new(30, 20));
+            let offset = (c as f32) - ((count - 1) as f32) / 2.0;
+            for point in &mut cloth.points {
+                point.x += offset * 14.0;
+                point.y -= (c as f32) * 4.0;
+            }
+
+            let (_, material) = Material::PRESETS[c % Material::PRESETS.len()];
+            cloth.apply_material(&material);
+            cloth.color = PALETTE[c % PALETTE.len()];
+            cloth
+        })
+        .collect();
 
-#[derive(Copy, Clone)]
-struct Vertex {
-    position: [f32; 2],
+    World::new(cloths)
 }
 
-implement_vertex!(Vertex, position);
+// create the world for the viewer or headless runner, either fresh or from a saved snapshot.
+// Cloth options apply to every cloth, `--hem-mass kg` weights the lowest points
+pub fn initial_world(args: &[String]) -> World {
+    let mut world = match (arg_value(args, "--load"), arg_value(args, "--cloths")) {
+        (Some(path), _) => World::load(path).expect("Failed to load snapshot"),
+        (None, Some(count)) => {
+            let count = count.parse().ok().filter(|&count| count > 0).expect("invalid --cloths, needs at least one");
+            staggered_cloths(count)
+        }
+        (None, None) => World::from(mesh_cloth(args)),
+    };
+
+    if args.iter().any(|arg| arg == "--collide") {
+        world.collide_cloths = true;
+    }
+
+    // drop a ball of this radius onto the middle of the scene
+    if let Some(radius) = arg_value(args, "--ball") {
+        let radius = radius.parse().expect("invalid --ball");
+        world.bodies.push(RigidBody::circle(0.0, 40.0 + radius, radius, 1.0));
+    }
 
-fn read_shader_src(path: &str) -> &'static str {
-    let mut src = String::new();
-    let mut file = fs::File::open(path).expect("Failed to open shader file");
-    file.read_to_string(&mut src).expect("Failed to read shader file");
+    for cloth in &mut world.cloths {
+        configure_cloth(cloth, args);
+    }
 
-    Box::leak(src.into_boxed_str())
+    // `--blobs n` balloons dropped in a row onto the scene, colliding with the cloth
+    if let Some(count) = arg_value(args, "--blobs") {
+        let count: usize = count.parse().expect("invalid --blobs");
+        for b in 0..count {
+            let offset = (b as f32) - ((count.max(1) - 1) as f32) / 2.0;
+            let mut blob = Blob { x: offset * 12.0, y: 45.0, ..Blob::default() }.build();
+            blob.color = PALETTE[world.cloths.len() % PALETTE.len()];
+            world.cloths.push(blob);
+        }
+        world.collide_cloths = true;
+    }
+
+    // `--jellies n` shape matched blocks, dropped the same way
+    if let Some(count) = arg_value(args, "--jellies") {
+        let count: usize = count.parse().expect("invalid --jellies");
+        for j in 0..count {
+            let offset = (j as f32) - ((count.max(1) - 1) as f32) / 2.0;
+            let mut jelly = Jelly { x: offset * 12.0, y: 55.0, ..Jelly::default() }.build();
+            jelly.color = PALETTE[world.cloths.len() % PALETTE.len()];
+            world.cloths.push(jelly);
+        }
+        world.collide_cloths = true;
+    }
+
+    world
+}
+
+fn configure_cloth(cloth: &mut Cloth, args: &[String]) {
+    if let Some(mass) = arg_value(args, "--hem-mass") {
+        cloth.set_hem_mass(mass.parse().expect("invalid --hem-mass"));
+    }
+
+    if let Some(name) = arg_value(args, "--material") {
+        let material = Material::preset(name).expect("unknown --material, try cotton, silk, rubber, chainmail or foil");
+        cloth.apply_material(&material);
+    }
+
+    // reproduce the original lab runs, dampers on absolute velocity and no separate air drag
+    if args.iter().any(|arg| arg == "--legacy-damping") {
+        cloth.damping = Damping::Legacy;
+    }
+
+    if let Some(air_drag) = arg_value(args, "--air-drag") {
+        cloth.air_drag = air_drag.parse().expect("invalid --air-drag");
+    }
+
+    // the elements `from_mesh` laid over the rest shape, so K can switch to them later. `--fem`
+    // starts with them, `--youngs E` and `--poisson nu` describe the membrane
+    if let Some(fem) = &mut cloth.fem {
+        if let Some(youngs_modulus) = arg_value(args, "--youngs") {
+            fem.youngs_modulus = youngs_modulus.parse().expect("invalid --youngs");
+        }
+        if let Some(poisson_ratio) = arg_value(args, "--poisson") {
+            fem.poisson_ratio = poisson_ratio.parse().expect("invalid --poisson");
+        }
+    }
+
+    if args.iter().any(|arg| arg == "--fem") {
+        cloth.force_model = ForceModel::Fem;
+    }
+
+    // add spring forces up colour by colour instead of point by point, to compare the two
+    if args.iter().any(|arg| arg == "--coloured") {
+        cloth.spring_parallelism = SpringParallelism::Coloured;
+    }
+
+    // e.g. `--wind 4,0` blows to the right
+    if let Some(wind) = arg_value(args, "--wind") {
+        let wind = wind
+            .split_once(',')
+            .and_then(|(x, y)| Some((x.parse().ok()?, y.parse().ok()?)))
+            .expect("invalid --wind, expected X,Y");
+        cloth.aerodynamics = Some(Aerodynamics { wind, ..Aerodynamics::default() });
+    }
+}
+
+// the mouse position in simulation units, undoing the renderer's scale and aspect correction
+fn mouse_world(mouse_pos: (f32, f32), window_size: (u32, u32), aspect_ratio: f32) -> (f32, f32) {
+    let x = ((mouse_pos.0 / (window_size.0 as f32)) * 2.0 - 1.0) / (0.03 * aspect_ratio);
+    let y = (-2.0 * (mouse_pos.1 / (window_size.1 as f32) - 0.5)) / 0.03;
+    (x, y)
 }
 
 fn main() {
     env::set_var("RUST_BACKTRACE", "0");
     println!("Core Count: {}", *CORE_COUNT);
 
+    let args: Vec<String> = env::args().collect();
+
+    if args.iter().any(|arg| arg == "--headless") {
+        headless::run(&args);
+        return;
+    }
+
     // create event loop
     let event_loop = winit::event_loop::EventLoopBuilder
         ::new()
@@ -45,46 +264,43 @@ fn main() {
         .with_inner_size(1280, 1000)
         .build(&event_loop);
 
-    let n = 50;
-    let m = 50;
+    // F5 / F9 save and load this file, its extension picks JSON or binary
+    let snapshot_path = PathBuf::from(arg_value(&args, "--snapshot").unwrap_or("snapshot.bin"));
 
-    // create cloth
-    let mut cloth = Cloth::new(n, m);
+    // R toggles recording, exported into this directory when it stops
+    let record_dir = PathBuf::from(arg_value(&args, "--record").unwrap_or("recording"));
+    let record_every: usize = arg_value(&args, "--every").map_or(4, |s|
+        s.parse().expect("invalid --every")
+    );
 
-    // Adjust coordinates to center the grid
-    let max_y = cloth.points
-        .iter()
-        .flat_map(|row| row.iter().map(|point| point.y))
-        .fold(f32::MIN, f32::max);
-
-    for row in &mut cloth.points {
-        for point in row {
-            point.x -= (m as f32) / 2.0;
-            point.y -= max_y;
-            point.y += 30.0; // extra offset
-        }
-    }
+    let world = initial_world(&args);
 
-    let vertex_shader_src = read_shader_src("src/shaders/vertex.glsl");
-    let fragment_shader_src = read_shader_src("src/shaders/fragment.glsl");
+    let program = render::build_program(&display);
 
-    let program = glium::Program
-        ::from_source(&display, &vertex_shader_src, &fragment_shader_src, None)
-        .unwrap();
+    // D toggles the live energy plot
+    let mut energy_graph = EnergyGraph::new(300);
+    // the snapshot step last added to the graph, a snapshot is read on every frame but only
+    // new steps belong on it
+    let mut graphed_step = 0;
+    let mut show_diagnostics = true;
 
     let mut mouse_pos = (0.0, 0.0);
     let mut closest_point = None;
+    let mut grabbing = false;
+    let mut heating = false;
     let mut window_size = (0, 0);
-    let mut matrix = [[0.0f32; 4]; 4];
     let mut aspect_ratio: f32 = 0.0;
 
     let mut fps_values = Vec::new();
-    let mut simulation_times = Vec::new();
     let mut frame_draw_times = Vec::new();
     let mut last_frame_time = Instant::now();
 
     let mut affected_point: Option<(usize, usize)> = None;
 
+    // simulation advances in fixed 0.01s steps on its own thread, rendering interpolates
+    // between the last two states it published
+    let mut simulation = SimulationThread::spawn(world, FixedTimestep::new(0.01, 20));
+
     // render loop
     let _ = event_loop.run(move |event, window_target| {
         match event {
@@ -99,12 +315,7 @@ fn main() {
                             println!("Average FPS: {}", avg_fps);
                         }
 
-                        if !simulation_times.is_empty() {
-                            let total_sims = simulation_times.len();
-                            let avg_time: f32 =
-                                simulation_times.iter().sum::<f32>() / (total_sims as f32);
-                            println!("Average Simulation Time: {} ms", avg_time);
-                        }
+                        simulation.stop().print_summary();
 
                         if !frame_draw_times.is_empty() {
                             let total_draws = frame_draw_times.len();
@@ -121,61 +332,140 @@ fn main() {
                     }
                     winit::event::WindowEvent::CursorMoved { position, .. } => {
                         mouse_pos = position.into();
-                    }
-                    winit::event::WindowEvent::MouseInput { state, button, .. } => {
-                        if button == winit::event::MouseButton::Left {
-                            if state == winit::event::ElementState::Pressed {
-                                // Iterate over cloth points to find the closest one to the mouse
-                                println!("Mouse Positions ({}, {})", mouse_pos.0, mouse_pos.1);
-
-                                let mouse_x = (mouse_pos.0 / (window_size.0 as f32)) * 2.0 - 1.0;
-                                let mouse_y = -2.0 * (mouse_pos.1 / (window_size.1 as f32) - 0.5);
-
-                                println!("Normalized Mouse Positions ({}, {})", mouse_x, mouse_y);
-
-                                println!("Aspect Ratio: {}", aspect_ratio);
-
-                                let closest = cloth.points
-                                    .iter()
-                                    .enumerate()
-                                    .flat_map(|(i, row)| {
-                                        row.iter()
-                                            .enumerate()
-                                            .map(move |(j, _)| (i, j))
-                                    })
-                                    .min_by(|&(i1, j1), &(i2, j2)| {
-                                        let point1 = &cloth.points[i1][j1];
-                                        let t_point_x1 = point1.x * 0.03 * aspect_ratio;
-                                        let t_point_y1 = point1.y * 0.03;
-                                        let dx1 = t_point_x1 - mouse_x;
-                                        let dy1 = t_point_y1 - mouse_y;
-                                        let distance1 = dx1 * dx1 + dy1 * dy1;
-
-                                        let point2 = &cloth.points[i2][j2];
-                                        let t_point_x2 = point2.x * 0.03 * aspect_ratio;
-                                        let t_point_y2 = point2.y * 0.03;
-                                        let dx2 = t_point_x2 - mouse_x;
-                                        let dy2 = t_point_y2 - mouse_y;
-                                        let distance2 = dx2 * dx2 + dy2 * dy2;
-
-                                        distance1.partial_cmp(&distance2).unwrap()
-                                    });
 
-                                println!("Closest: {:?}", closest);
+                        if grabbing {
+                            let (x, y) = mouse_world(mouse_pos, window_size, aspect_ratio);
+                            simulation.send(Command::DragTo { x, y });
+                        }
 
-                                if let Some((i, j)) = closest {
-                                    cloth.points[i][j].ext_m += 10.0;
-                                    closest_point = Some((i, j));
-                                }
-                            } else if state == winit::event::ElementState::Released {
-                                if let Some((i, j)) = closest_point {
-                                    cloth.points[i][j].ext_m = 0.0;
-                                }
-                                closest_point = None;
+                        if heating {
+                            let (x, y) = mouse_world(mouse_pos, window_size, aspect_ratio);
+                            simulation.send(Command::HeatGun(Some([x, y])));
+                        }
+                    }
+                    // hold the middle button to heat the cloth under the mouse
+                    winit::event::WindowEvent::MouseInput {
+                        state,
+                        button: winit::event::MouseButton::Middle,
+                        ..
+                    } => {
+                        heating = state == winit::event::ElementState::Pressed;
+                        let target = heating.then(|| {
+                            let (x, y) = mouse_world(mouse_pos, window_size, aspect_ratio);
+                            [x, y]
+                        });
+                        simulation.send(Command::HeatGun(target));
+                    }
+                    // hold the right button to drag the nearest point around, e.g. to swing a rope
+                    winit::event::WindowEvent::MouseInput {
+                        state,
+                        button: winit::event::MouseButton::Right,
+                        ..
+                    } => {
+                        if state == winit::event::ElementState::Pressed {
+                            let (x, y) = mouse_world(mouse_pos, window_size, aspect_ratio);
+                            let snapshot = simulation.snapshots.read();
+
+                            let nearest = snapshot.positions
+                                .iter()
+                                .enumerate()
+                                .min_by(|(_, p1), (_, p2)| {
+                                    let distance1 = (p1[0] - x).powi(2) + (p1[1] - y).powi(2);
+                                    let distance2 = (p2[0] - x).powi(2) + (p2[1] - y).powi(2);
+                                    distance1.total_cmp(&distance2)
+                                })
+                                .and_then(|(index, _)| World::locate(&snapshot.layout, index));
+
+                            if let Some((cloth, point)) = nearest {
+                                simulation.send(Command::Grab { cloth, point, x, y });
+                                grabbing = true;
                             }
+                        } else {
+                            simulation.send(Command::Release);
+                            grabbing = false;
+                        }
+                    }
+                    winit::event::WindowEvent::MouseInput {
+                        state,
+                        button: winit::event::MouseButton::Left,
+                        ..
+                    } => {
+                        if state == winit::event::ElementState::Pressed {
+                            // Iterate over cloth points to find the closest one to the mouse
+                            println!("Mouse Positions ({}, {})", mouse_pos.0, mouse_pos.1);
+
+                            let mouse_x = (mouse_pos.0 / (window_size.0 as f32)) * 2.0 - 1.0;
+                            let mouse_y = -2.0 * (mouse_pos.1 / (window_size.1 as f32) - 0.5);
+
+                            println!("Normalized Mouse Positions ({}, {})", mouse_x, mouse_y);
+
+                            println!("Aspect Ratio: {}", aspect_ratio);
+
+                            let snapshot = simulation.snapshots.read();
+
+                            let closest = snapshot.positions
+                                .iter()
+                                .enumerate()
+                                .min_by(|(_, point1), (_, point2)| {
+                                    let t_point_x1 = point1[0] * 0.03 * aspect_ratio;
+                                    let t_point_y1 = point1[1] * 0.03;
+                                    let dx1 = t_point_x1 - mouse_x;
+                                    let dy1 = t_point_y1 - mouse_y;
+                                    let distance1 = dx1 * dx1 + dy1 * dy1;
+
+                                    let t_point_x2 = point2[0] * 0.03 * aspect_ratio;
+                                    let t_point_y2 = point2[1] * 0.03;
+                                    let dx2 = t_point_x2 - mouse_x;
+                                    let dy2 = t_point_y2 - mouse_y;
+                                    let distance2 = dx2 * dx2 + dy2 * dy2;
+
+                                    distance1.partial_cmp(&distance2).unwrap()
+                                })
+                                .and_then(|(index, _)| World::locate(&snapshot.layout, index));
+
+                            println!("Closest: {:?}", closest);
+
+                            if let Some((cloth, point)) = closest {
+                                simulation.send(Command::SetExternalForce {
+                                    cloth,
+                                    point,
+                                    magnitude: 10.0,
+                                });
+                                closest_point = closest;
+                            }
+                        } else if state == winit::event::ElementState::Released {
+                            if let Some((cloth, point)) = closest_point {
+                                simulation.send(Command::SetExternalForce {
+                                    cloth,
+                                    point,
+                                    magnitude: 0.0,
+                                });
+                            }
+                            closest_point = None;
                         }
                     }
                     winit::event::WindowEvent::KeyboardInput { event, .. } => {
+                        if
+                            event.logical_key ==
+                                winit::keyboard::Key::Named(winit::keyboard::NamedKey::Space) &&
+                            event.state == winit::event::ElementState::Pressed &&
+                            !event.repeat
+                        {
+                            simulation.send(Command::TogglePause);
+                        }
+
+                        if event.state == winit::event::ElementState::Pressed && !event.repeat {
+                            match event.logical_key {
+                                winit::keyboard::Key::Named(winit::keyboard::NamedKey::F5) => {
+                                    simulation.send(Command::Save(snapshot_path.clone()));
+                                }
+                                winit::keyboard::Key::Named(winit::keyboard::NamedKey::F9) => {
+                                    simulation.send(Command::Load(snapshot_path.clone()));
+                                }
+                                _ => (),
+                            }
+                        }
+
                         if
                             let winit::event::KeyEvent {
                                 state,
@@ -187,7 +477,95 @@ fn main() {
                                 c.to_lowercase() == "g" &&
                                 state == winit::event::ElementState::Pressed
                             {
-                                cloth.g_on = !cloth.g_on;
+                                simulation.send(Command::ToggleGravity);
+                            }
+
+                            if state == winit::event::ElementState::Pressed {
+                                let time_scale = match c.as_str() {
+                                    "0" => Some(0.0),
+                                    "1" => Some(1.0),
+                                    "2" => Some(2.0),
+                                    "s" | "S" => Some(0.25),
+                                    _ => None,
+                                };
+
+                                if let Some(time_scale) = time_scale {
+                                    simulation.send(Command::SetTimeScale(time_scale));
+                                    println!("Time Scale: {}x", time_scale);
+                                }
+
+                                if c.to_lowercase() == "a" && !event.repeat {
+                                    simulation.send(Command::ToggleAdaptive);
+                                }
+
+                                if c.to_lowercase() == "h" && !event.repeat {
+                                    simulation.send(Command::CycleHeatmap);
+                                }
+
+                                // drop a ball or a box where the mouse is
+                                if (c.to_lowercase() == "b" || c.to_lowercase() == "v") && !event.repeat {
+                                    let (x, y) = mouse_world(mouse_pos, window_size, aspect_ratio);
+                                    let body = if c.to_lowercase() == "b" {
+                                        RigidBody::circle(x, y, 4.0, 1.0)
+                                    } else {
+                                        RigidBody::rect(x, y, 8.0, 5.0, 1.0)
+                                    };
+                                    simulation.send(Command::DropBody(body));
+                                }
+
+                                // a balloon where the mouse is
+                                if c.to_lowercase() == "o" && !event.repeat {
+                                    let (x, y) = mouse_world(mouse_pos, window_size, aspect_ratio);
+                                    let mut blob = Blob { x, y, ..Blob::default() }.build();
+                                    blob.color = PALETTE[1];
+                                    simulation.send(Command::AddCloth(Box::new(blob)));
+                                }
+
+                                // and a block of jelly
+                                if c.to_lowercase() == "j" && !event.repeat {
+                                    let (x, y) = mouse_world(mouse_pos, window_size, aspect_ratio);
+                                    let mut jelly = Jelly { x, y, ..Jelly::default() }.build();
+                                    jelly.color = PALETTE[2];
+                                    simulation.send(Command::AddCloth(Box::new(jelly)));
+                                }
+
+                                if c.to_lowercase() == "c" && !event.repeat {
+                                    simulation.send(Command::ToggleClothCollisions);
+                                }
+
+                                if c.to_lowercase() == "w" && !event.repeat {
+                                    simulation.send(Command::ToggleWind);
+                                }
+
+                                if c.to_lowercase() == "k" && !event.repeat {
+                                    simulation.send(Command::ToggleForceModel);
+                                }
+
+                                if c.to_lowercase() == "l" && !event.repeat {
+                                    simulation.send(Command::ToggleDamping);
+                                }
+
+                                if c.to_lowercase() == "m" && !event.repeat {
+                                    simulation.send(Command::CycleMaterial);
+                                }
+
+                                if c.to_lowercase() == "d" && !event.repeat {
+                                    show_diagnostics = !show_diagnostics;
+                                }
+
+                                if c.to_lowercase() == "r" && !event.repeat {
+                                    simulation.send(Command::ToggleRecording {
+                                        dir: record_dir.clone(),
+                                        every: record_every,
+                                    });
+                                }
+
+                                // step forwards / scrub backwards while paused, holding repeats
+                                match c.as_str() {
+                                    "." => simulation.send(Command::Step),
+                                    "," => simulation.send(Command::Rewind),
+                                    _ => (),
+                                }
                             }
 
                             if c.to_lowercase() == "f" {
@@ -195,14 +573,27 @@ fn main() {
                                     state == winit::event::ElementState::Pressed &&
                                     affected_point.is_none()
                                 {
-                                    let i = rand::random::<usize>() % cloth.points.len();
-                                    let j = rand::random::<usize>() % cloth.points[0].len();
-
-                                    cloth.points[i][j].ext_m += 10.0;
-                                    affected_point = Some((i, j));
+                                    let snapshot = simulation.snapshots.read();
+                                    // nothing to pick from a world without points
+                                    let picked = rand::random::<usize>()
+                                        .checked_rem(snapshot.positions.len())
+                                        .and_then(|index| World::locate(&snapshot.layout, index));
+
+                                    if let Some((cloth, point)) = picked {
+                                        simulation.send(Command::SetExternalForce {
+                                            cloth,
+                                            point,
+                                            magnitude: 10.0,
+                                        });
+                                        affected_point = Some((cloth, point));
+                                    }
                                 } else if state == winit::event::ElementState::Released {
-                                    if let Some((i, j)) = affected_point {
-                                        cloth.points[i][j].ext_m = 0.0;
+                                    if let Some((cloth, point)) = affected_point {
+                                        simulation.send(Command::SetExternalForce {
+                                            cloth,
+                                            point,
+                                            magnitude: 0.0,
+                                        });
                                     }
                                     affected_point = None;
                                 }
@@ -223,62 +614,27 @@ fn main() {
 
                         aspect_ratio = (height as f32) / (width as f32);
 
-                        matrix = [
-                            [0.03 * aspect_ratio, 0.0, 0.0, 0.0],
-                            [0.0, 0.03, 0.0, 0.0],
-                            [0.0, 0.0, 1.0, 0.0],
-                            [0.0, 0.0, 0.0, 1.0f32],
-                        ];
-
-                        let uniforms =
-                            uniform! {
-                            matrix: matrix,
-                        };
-
-                        // convert to vertices and indices
-                        let vertices: Vec<Vertex> = cloth.points
-                            .iter()
-                            .flat_map(|row| {
-                                row.iter().map(|point| Vertex { position: [point.x, point.y] })
-                            })
-                            .collect();
-
-                        let indices: Vec<u16> = cloth.springs
-                            .iter()
-                            .flat_map(|spring| {
-                                vec![
-                                    (spring.p1.0 as u16) * (m as u16) + (spring.p1.1 as u16),
-                                    (spring.p2.0 as u16) * (m as u16) + (spring.p2.1 as u16)
-                                ]
-                            })
-                            .collect();
-
-                        // create vertex and index buffer
-                        let vertex_buffer = VertexBuffer::new(&display, &vertices).unwrap();
-                        let index_buffer = glium::IndexBuffer
-                            ::new(&display, glium::index::PrimitiveType::LinesList, &indices)
-                            .unwrap();
-
-                        // update simulation
-                        for _ in 0..10 {
-                            let sim_start = Instant::now();
-                            cloth.simulate_multithreaded(0.01);
-                            // cloth.simulate(0.01);
-                            let sim_end = Instant::now();
-                            let sim_time = sim_end.duration_since(sim_start).as_micros();
-                            simulation_times.push((sim_time as f32) / 1000.0); // convert to millis
-                        }
+                        // draw the latest published state
+                        let snapshot = simulation.snapshots.read();
 
                         let draw_start = Instant::now();
-                        target
-                            .draw(
-                                &vertex_buffer,
-                                &index_buffer,
-                                &program,
-                                &uniforms,
-                                &Default::default()
-                            )
-                            .unwrap();
+                        render::draw_springs(
+                            &mut target,
+                            &display,
+                            &program,
+                            snapshot.interpolated(),
+                            snapshot.heat.as_deref().map(Vec::as_slice),
+                            &snapshot.layout,
+                            aspect_ratio
+                        );
+                        render::draw_bodies(&mut target, &display, &program, &snapshot.bodies, aspect_ratio);
+                        if snapshot.step > graphed_step {
+                            graphed_step = snapshot.step;
+                            energy_graph.push(snapshot.diagnostics);
+                        }
+                        if show_diagnostics {
+                            energy_graph.draw(&mut target, &display, &program);
+                        }
                         let draw_end = Instant::now();
                         let draw_time = draw_end.duration_since(draw_start).as_micros();
                         frame_draw_times.push((draw_time as f32) / 1000.0); // convert to millis
@@ -0,0 +1,135 @@
+use serde::{ Deserialize, Serialize };
+
+use crate::cloth::Spring;
+
+// fabric behaviour, warp runs vertically and weft horizontally. Past
+// `stiffening_strain` the fibres straighten and the springs get `stiffening_ratio` times stiffer,
+// past `yield_strain` they start to deform for good at `plastic_flow`. `thermal_expansion` is
+// the relative change of length per degree, most fibres shrink as they heat up
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Material {
+    pub warp_stiffness: f32,
+    pub warp_damping: f32,
+    pub weft_stiffness: f32,
+    pub weft_damping: f32,
+    pub stiffening_strain: f32,
+    pub stiffening_ratio: f32,
+    pub yield_strain: f32,
+    pub plastic_flow: f32,
+    pub thermal_expansion: f32,
+}
+
+impl Default for Material {
+    // the original lab constants, linear and the same in both directions
+    fn default() -> Self {
+        Material {
+            warp_stiffness: 10.0,
+            warp_damping: 0.03,
+            weft_stiffness: 10.0,
+            weft_damping: 0.03,
+            stiffening_strain: f32::MAX,
+            stiffening_ratio: 1.0,
+            yield_strain: f32::MAX,
+            plastic_flow: 0.0,
+            thermal_expansion: -0.002,
+        }
+    }
+}
+
+impl Material {
+    // woven, a little stiffer along the warp, locks up quickly once the weave straightens
+    pub const COTTON: Material = Material {
+        warp_stiffness: 12.0,
+        warp_damping: 0.04,
+        weft_stiffness: 9.0,
+        weft_damping: 0.04,
+        stiffening_strain: 0.05,
+        stiffening_ratio: 3.0,
+        yield_strain: f32::MAX,
+        plastic_flow: 0.0,
+        thermal_expansion: -0.002,
+    };
+
+    // light and drapey, soft until well stretched
+    pub const SILK: Material = Material {
+        warp_stiffness: 6.0,
+        warp_damping: 0.015,
+        weft_stiffness: 5.0,
+        weft_damping: 0.015,
+        stiffening_strain: 0.1,
+        stiffening_ratio: 2.5,
+        yield_strain: f32::MAX,
+        plastic_flow: 0.0,
+        thermal_expansion: -0.001,
+    };
+
+    // isotropic and stretchy, no fibres to straighten, pulls tighter when warm
+    pub const RUBBER: Material = Material {
+        warp_stiffness: 4.0,
+        warp_damping: 0.06,
+        weft_stiffness: 4.0,
+        weft_damping: 0.06,
+        stiffening_strain: f32::MAX,
+        stiffening_ratio: 1.0,
+        yield_strain: f32::MAX,
+        plastic_flow: 0.0,
+        thermal_expansion: -0.003,
+    };
+
+    // rings slide freely until they bind, then barely stretch at all
+    pub const CHAINMAIL: Material = Material {
+        warp_stiffness: 8.0,
+        warp_damping: 0.05,
+        weft_stiffness: 8.0,
+        weft_damping: 0.05,
+        stiffening_strain: 0.02,
+        stiffening_ratio: 5.0,
+        yield_strain: f32::MAX,
+        plastic_flow: 0.0,
+        thermal_expansion: 0.00001,
+    };
+
+    // thin metal sheet, stiff but gives way for good past 2% and keeps its creases
+    pub const FOIL: Material = Material {
+        warp_stiffness: 20.0,
+        warp_damping: 0.05,
+        weft_stiffness: 20.0,
+        weft_damping: 0.05,
+        stiffening_strain: f32::MAX,
+        stiffening_ratio: 1.0,
+        yield_strain: 0.02,
+        plastic_flow: 5.0,
+        thermal_expansion: 0.00002,
+    };
+
+    pub const PRESETS: [(&'static str, Material); 5] = [
+        ("cotton", Material::COTTON),
+        ("silk", Material::SILK),
+        ("rubber", Material::RUBBER),
+        ("chainmail", Material::CHAINMAIL),
+        ("foil", Material::FOIL),
+    ];
+
+    pub fn preset(name: &str) -> Option<Material> {
+        Material::PRESETS.iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+            .map(|&(_, material)| material)
+    }
+
+    // give a spring this material's properties, warp or weft as the spring was laid out
+    pub fn apply(&self, spring: &mut Spring) {
+        if spring.warp {
+            spring.spring_coeff = self.warp_stiffness;
+            spring.damp_coeff = self.warp_damping;
+        } else {
+            spring.spring_coeff = self.weft_stiffness;
+            spring.damp_coeff = self.weft_damping;
+        }
+
+        spring.stiffening_strain = self.stiffening_strain;
+        spring.stiffening_ratio = self.stiffening_ratio;
+        spring.yield_strain = self.yield_strain;
+        spring.plastic_flow = self.plastic_flow;
+        spring.thermal_expansion = self.thermal_expansion;
+    }
+}
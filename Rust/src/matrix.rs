@@ -0,0 +1,39 @@
+// 2x2 matrices for the finite elements and shape matching, row major
+pub type Matrix = [[f32; 2]; 2];
+
+// the rotation part R of the polar decomposition A = R S. In 2D it's just the rotation by the
+// angle of (A00 + A11, A10 - A01)
+pub fn polar_rotation(a: &Matrix) -> Matrix {
+    let angle = (a[1][0] - a[0][1]).atan2(a[0][0] + a[1][1]);
+    let (sin, cos) = angle.sin_cos();
+    [
+        [cos, -sin],
+        [sin, cos],
+    ]
+}
+
+pub fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    [
+        [a[0][0] * b[0][0] + a[0][1] * b[1][0], a[0][0] * b[0][1] + a[0][1] * b[1][1]],
+        [a[1][0] * b[0][0] + a[1][1] * b[1][0], a[1][0] * b[0][1] + a[1][1] * b[1][1]],
+    ]
+}
+
+pub fn transpose(a: &Matrix) -> Matrix {
+    [
+        [a[0][0], a[1][0]],
+        [a[0][1], a[1][1]],
+    ]
+}
+
+pub fn symmetric(a: &Matrix) -> Matrix {
+    let off_diagonal = (a[0][1] + a[1][0]) / 2.0;
+    [
+        [a[0][0], off_diagonal],
+        [off_diagonal, a[1][1]],
+    ]
+}
+
+pub fn determinant(a: &Matrix) -> f32 {
+    a[0][0] * a[1][1] - a[0][1] * a[1][0]
+}
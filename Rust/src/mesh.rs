@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::cloth::invalid_data;
+
+// positions and connectivity for a cloth before it gets masses and springs. Every edge
+// becomes a spring, triangles are only used for the aerodynamics
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub positions: Vec<[f32; 2]>,
+    pub edges: Vec<(usize, usize)>,
+    pub triangles: Vec<[usize; 3]>,
+}
+
+// builds a mesh out of shapes that share corners, merging vertices that land on the same spot
+// and edges that are added twice
+#[derive(Default)]
+struct MeshBuilder {
+    mesh: Mesh,
+    vertices: HashMap<(i32, i32), usize>,
+    edges: HashMap<(usize, usize), usize>,
+}
+
+impl MeshBuilder {
+    fn vertex(&mut self, x: f32, y: f32) -> usize {
+        // a thousandth of the spacing is plenty to tell lattice points apart
+        let key = ((x * 1000.0).round() as i32, (y * 1000.0).round() as i32);
+        let positions = &mut self.mesh.positions;
+
+        *self.vertices.entry(key).or_insert_with(|| {
+            positions.push([x, y]);
+            positions.len() - 1
+        })
+    }
+
+    fn edge(&mut self, a: usize, b: usize) {
+        if a == b {
+            return;
+        }
+
+        let edges = &mut self.mesh.edges;
+        self.edges.entry((a.min(b), a.max(b))).or_insert_with(|| {
+            edges.push((a, b));
+            edges.len() - 1
+        });
+    }
+
+    // a triangle and its three edges
+    fn triangle(&mut self, a: usize, b: usize, c: usize) {
+        self.mesh.triangles.push([a, b, c]);
+        self.edge(a, b);
+        self.edge(b, c);
+        self.edge(c, a);
+    }
+
+    fn build(self) -> Mesh {
+        self.mesh
+    }
+}
+
+impl Mesh {
+    // `rows` x `cols` points, row 0 at the bottom. Springs only run along rows and columns,
+    // the triangles split each cell along alternating diagonals so they have no bias
+    pub fn grid(rows: usize, cols: usize, spacing: f32) -> Mesh {
+        let positions = (0..rows)
+            .flat_map(|i| (0..cols).map(move |j| [(j as f32) * spacing, (i as f32) * spacing]))
+            .collect();
+
+        let mut edges = Vec::new();
+        let mut triangles = Vec::new();
+        for i in 0..rows {
+            for j in 0..cols {
+                let index = i * cols + j;
+
+                if i < rows - 1 {
+                    edges.push((index, index + cols));
+                }
+
+                if j < cols - 1 {
+                    edges.push((index, index + 1));
+                }
+
+                if i < rows - 1 && j < cols - 1 {
+                    let (bottom_left, bottom_right) = (index, index + 1);
+                    let (top_left, top_right) = (index + cols, index + cols + 1);
+
+                    if (i + j).is_multiple_of(2) {
+                        triangles.push([bottom_left, top_right, bottom_right]);
+                        triangles.push([bottom_left, top_right, top_left]);
+                    } else {
+                        triangles.push([bottom_right, top_left, bottom_left]);
+                        triangles.push([bottom_right, top_left, top_right]);
+                    }
+                }
+            }
+        }
+
+        Mesh { positions, edges, triangles }
+    }
+
+    // equilateral triangles, every other row shifted by half a spacing. Holds its shape better
+    // than the grid since every cell is braced
+    pub fn triangle_lattice(rows: usize, cols: usize, spacing: f32) -> Mesh {
+        let height = spacing * (3.0f32).sqrt() / 2.0;
+        let mut builder = MeshBuilder::default();
+
+        let position = |i: usize, j: usize| {
+            let shift = if i % 2 == 1 { spacing / 2.0 } else { 0.0 };
+            ((j as f32) * spacing + shift, (i as f32) * height)
+        };
+
+        for i in 0..rows {
+            for j in 0..cols {
+                let (x, y) = position(i, j);
+                builder.vertex(x, y);
+            }
+        }
+
+        for i in 0..rows.saturating_sub(1) {
+            for j in 0..cols.saturating_sub(1) {
+                let corner = |builder: &mut MeshBuilder, i, j| {
+                    let (x, y) = position(i, j);
+                    builder.vertex(x, y)
+                };
+
+                let bottom_left = corner(&mut builder, i, j);
+                let bottom_right = corner(&mut builder, i, j + 1);
+                let top_left = corner(&mut builder, i + 1, j);
+                let top_right = corner(&mut builder, i + 1, j + 1);
+
+                // the row above leans right of even rows and left of odd ones
+                if i % 2 == 0 {
+                    builder.triangle(bottom_left, bottom_right, top_left);
+                    builder.triangle(bottom_right, top_right, top_left);
+                } else {
+                    builder.triangle(bottom_left, top_right, top_left);
+                    builder.triangle(bottom_left, bottom_right, top_right);
+                }
+            }
+        }
+
+        builder.build()
+    }
+
+    // a honeycomb of `rows` x `cols` hexagonal cells with sides of length `spacing`. Only the
+    // cell walls are springs, so it shears easily, the triangles fan out from one corner of each
+    // cell
+    pub fn hexagonal(rows: usize, cols: usize, spacing: f32) -> Mesh {
+        let width = spacing * (3.0f32).sqrt();
+        let mut builder = MeshBuilder::default();
+
+        for i in 0..rows {
+            for j in 0..cols {
+                // pointy-topped cells, odd rows tuck in between the ones below
+                let shift = if i % 2 == 1 { width / 2.0 } else { 0.0 };
+                let cx = (j as f32) * width + shift;
+                let cy = (i as f32) * spacing * 1.5;
+
+                let corners: Vec<usize> = (0..6)
+                    .map(|k| {
+                        let angle = ((k as f32) / 6.0) * TAU + TAU / 12.0;
+                        builder.vertex(cx + spacing * angle.cos(), cy + spacing * angle.sin())
+                    })
+                    .collect();
+
+                for k in 1..5 {
+                    builder.mesh.triangles.push([corners[0], corners[k], corners[k + 1]]);
+                }
+                for k in 0..6 {
+                    builder.edge(corners[k], corners[(k + 1) % 6]);
+                }
+            }
+        }
+
+        builder.build()
+    }
+
+    // a disc of `rings` concentric rings around a centre point, ring k holding 6k points so
+    // neighbours stay roughly `spacing` apart
+    pub fn circle(rings: usize, spacing: f32) -> Mesh {
+        let mut builder = MeshBuilder::default();
+        let centre = builder.vertex(0.0, 0.0);
+
+        let mut inner = vec![centre];
+        for k in 1..=rings {
+            let count = 6 * k;
+            let radius = (k as f32) * spacing;
+            let outer: Vec<usize> = (0..count)
+                .map(|j| {
+                    let angle = ((j as f32) / (count as f32)) * TAU;
+                    builder.vertex(radius * angle.cos(), radius * angle.sin())
+                })
+                .collect();
+
+            stitch_rings(&mut builder, &inner, &outer);
+            inner = outer;
+        }
+
+        builder.build()
+    }
+
+    // `count` points evenly around a circle, each joined to the next and the last back to the
+    // first. Hollow, so it has no triangles
+    pub fn ring(count: usize, radius: f32) -> Mesh {
+        let positions = (0..count)
+            .map(|i| {
+                let angle = ((i as f32) / (count as f32)) * TAU;
+                [radius * angle.cos(), radius * angle.sin()]
+            })
+            .collect();
+        let edges = (0..count).map(|i| (i, (i + 1) % count)).collect();
+
+        Mesh { positions, edges, triangles: Vec::new() }
+    }
+
+    // a straight chain of `segments` springs running right from the origin
+    pub fn rope(segments: usize, spacing: f32) -> Mesh {
+        let positions = (0..=segments).map(|i| [(i as f32) * spacing, 0.0]).collect();
+        let edges = (0..segments).map(|i| (i, i + 1)).collect();
+
+        Mesh { positions, edges, triangles: Vec::new() }
+    }
+
+    // a 2D mesh from Wavefront OBJ, taking x and y of each vertex. Faces are fanned into
+    // triangles and their sides become springs, `l` elements add springs on their own
+    pub fn load_obj(path: impl AsRef<Path>) -> io::Result<Mesh> {
+        Mesh::parse_obj(&fs::read_to_string(path)?).map_err(invalid_data)
+    }
+
+    pub fn parse_obj(text: &str) -> Result<Mesh, String> {
+        let mut builder = MeshBuilder::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let mut fields = line.split_whitespace();
+            let error = |message: &str| format!("line {}: {}", number + 1, message);
+
+            match fields.next() {
+                Some("v") => {
+                    let mut coordinate = || -> Result<f32, String> {
+                        fields
+                            .next()
+                            .and_then(|field| field.parse().ok())
+                            .ok_or_else(|| error("expected a vertex coordinate"))
+                    };
+                    let (x, y) = (coordinate()?, coordinate()?);
+
+                    // no merging here, the file's own numbering has to be kept
+                    builder.mesh.positions.push([x, y]);
+                }
+                Some(element @ ("f" | "l")) => {
+                    let num_vertices = builder.mesh.positions.len();
+                    let indices = fields
+                        .map(|field| obj_index(field, num_vertices).ok_or_else(|| error("bad vertex index")))
+                        .collect::<Result<Vec<usize>, String>>()?;
+
+                    if element == "f" {
+                        if indices.len() < 3 {
+                            return Err(error("a face needs at least three vertices"));
+                        }
+
+                        // springs around the outline, the fan triangles only catch the wind
+                        for k in 1..indices.len() - 1 {
+                            builder.mesh.triangles.push([indices[0], indices[k], indices[k + 1]]);
+                        }
+                        for k in 0..indices.len() {
+                            builder.edge(indices[k], indices[(k + 1) % indices.len()]);
+                        }
+                    } else {
+                        for pair in indices.windows(2) {
+                            builder.edge(pair[0], pair[1]);
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let mesh = builder.build();
+        if mesh.positions.is_empty() {
+            return Err("no vertices".to_string());
+        }
+
+        Ok(mesh)
+    }
+}
+
+// `f 1/2/3 ...` style reference, 1-based or negative from the end
+fn obj_index(field: &str, num_vertices: usize) -> Option<usize> {
+    let index: i64 = field.split('/').next()?.parse().ok()?;
+
+    let index = if index < 0 { (num_vertices as i64) + index } else { index - 1 };
+    (0..num_vertices as i64).contains(&index).then_some(index as usize)
+}
+
+// triangulate the band between two rings, walking both in angle order and always advancing
+// the one whose next point comes first
+fn stitch_rings(builder: &mut MeshBuilder, inner: &[usize], outer: &[usize]) {
+    if inner.len() == 1 {
+        for j in 0..outer.len() {
+            builder.triangle(inner[0], outer[j], outer[(j + 1) % outer.len()]);
+        }
+        return;
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < inner.len() || j < outer.len() {
+        let next_inner = ((i + 1) as f32) / (inner.len() as f32);
+        let next_outer = ((j + 1) as f32) / (outer.len() as f32);
+
+        if j < outer.len() && (i == inner.len() || next_outer <= next_inner) {
+            builder.triangle(inner[i % inner.len()], outer[j], outer[(j + 1) % outer.len()]);
+            j += 1;
+        } else {
+            builder.triangle(inner[i], inner[(i + 1) % inner.len()], outer[j % outer.len()]);
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cloth::Cloth;
+    use crate::material::Material;
+
+    fn edge_set(mesh: &Mesh) -> Vec<(usize, usize)> {
+        let mut edges: Vec<(usize, usize)> = mesh.edges.iter().map(|&(a, b)| (a.min(b), a.max(b))).collect();
+        edges.sort();
+        edges
+    }
+
+    #[test]
+    fn obj_faces_and_lines_become_edges() {
+        let text = "v 0 0\nv 1 0\nv 1 1\nv 0 1\nv 2 0\nf 1 2 3 4\nf 2/1 5/2 -3\nl 5 1\n";
+        let mesh = Mesh::parse_obj(text).unwrap();
+
+        // the quad is fanned into two triangles, the side shared with the second face is
+        // only one spring
+        assert_eq!(mesh.triangles, vec![[0, 1, 2], [0, 2, 3], [1, 4, 2]]);
+        assert_eq!(edge_set(&mesh), vec![(0, 1), (0, 3), (0, 4), (1, 2), (1, 4), (2, 3), (2, 4)]);
+        assert_eq!(Cloth::from_mesh(&mesh, &Material::default()).springs.len(), 7);
+    }
+
+    #[test]
+    fn obj_indices_past_the_vertices_are_rejected() {
+        let error = Mesh::parse_obj("v 0 0\nv 1 0\nv 1 1\nf 1 2 4\n").err().unwrap();
+        assert!(error.starts_with("line 4"));
+        assert!(Mesh::parse_obj("v 0 0\nv 1 0\nl 1 0\n").is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use std::error::Error;
+use std::ffi::{ c_void, CString };
+use std::fs::File;
+use std::io::BufWriter;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::rc::Rc;
+
+use glium::backend::{ Backend, Context };
+use glium::debug::DebugCallbackBehavior;
+use glium::glutin::api::egl::context::PossiblyCurrentContext;
+use glium::glutin::api::egl::device::Device;
+use glium::glutin::api::egl::display::Display;
+use glium::glutin::config::{ ConfigSurfaceTypes, ConfigTemplateBuilder };
+use glium::glutin::context::{ ContextApi, ContextAttributesBuilder, Version };
+use glium::glutin::prelude::*;
+use glium::texture::{ RawImage2d, Texture2d };
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::{ Program, Surface, SwapBuffersError };
+
+use crate::render;
+use crate::world::World;
+
+// an EGL context with no window or pbuffer behind it, everything is drawn into textures
+struct SurfacelessBackend {
+    display: Display,
+    context: PossiblyCurrentContext,
+    size: (u32, u32),
+}
+
+unsafe impl Backend for SurfacelessBackend {
+    fn swap_buffers(&self) -> Result<(), SwapBuffersError> {
+        // there is no default framebuffer to present
+        Ok(())
+    }
+
+    unsafe fn get_proc_address(&self, symbol: &str) -> *const c_void {
+        let symbol = CString::new(symbol).unwrap();
+        self.display.get_proc_address(&symbol)
+    }
+
+    fn get_framebuffer_dimensions(&self) -> (u32, u32) {
+        self.size
+    }
+
+    fn resize(&self, _new_size: (u32, u32)) {}
+
+    fn is_current(&self) -> bool {
+        self.context.is_current()
+    }
+
+    unsafe fn make_current(&self) {
+        self.context.make_current_surfaceless().unwrap();
+    }
+}
+
+// renders the cloth into a texture and writes it out as PNG, no display server needed
+pub struct OffscreenRenderer {
+    context: Rc<Context>,
+    program: Program,
+    texture: Texture2d,
+    width: u32,
+    height: u32,
+}
+
+impl OffscreenRenderer {
+    pub fn new(width: u32, height: u32) -> Result<Self, Box<dyn Error>> {
+        // the first EGL device that will give us a context, e.g. a GPU or Mesa's llvmpipe
+        let device = Device::query_devices()?
+            .next()
+            .ok_or("no EGL devices available")?;
+        let display = unsafe { Display::with_device(&device, None)? };
+
+        let template = ConfigTemplateBuilder::new()
+            .with_surface_type(ConfigSurfaceTypes::empty())
+            .build();
+        let config = unsafe { display.find_configs(template)? }
+            .next()
+            .ok_or("no suitable EGL config")?;
+
+        let attributes = ContextAttributesBuilder::new()
+            .with_context_api(ContextApi::OpenGl(Some(Version::new(3, 1))))
+            .build(None);
+        let context = unsafe { display.create_context(&config, &attributes)? };
+        let context = context.make_current_surfaceless()?;
+
+        let backend = SurfacelessBackend { display, context, size: (width, height) };
+        let context = unsafe { Context::new(backend, true, DebugCallbackBehavior::Ignore)? };
+
+        let program = render::build_program(&context);
+        let texture = Texture2d::empty(&context, width, height)?;
+
+        Ok(OffscreenRenderer { context, program, texture, width, height })
+    }
+
+    // draw one frame and read it back as tightly packed top-to-bottom RGBA rows
+    pub fn render(&self, world: &World) -> Vec<u8> {
+        let mut target = SimpleFrameBuffer::new(&self.context, &self.texture).unwrap();
+        target.clear_color(1.0, 1.0, 1.0, 1.0);
+
+        let aspect_ratio = (self.height as f32) / (self.width as f32);
+        render::draw_springs(
+            &mut target,
+            &self.context,
+            &self.program,
+            world.positions().into_iter(),
+            None,
+            &world.layout(),
+            aspect_ratio
+        );
+        render::draw_bodies(&mut target, &self.context, &self.program, &world.bodies, aspect_ratio);
+
+        let image: RawImage2d<u8> = self.texture.read();
+
+        // GL reads bottom row first
+        let row_len = (self.width as usize) * 4;
+        image.data
+            .chunks(row_len)
+            .rev()
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    pub fn write_png(&self, world: &World, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let pixels = self.render(world);
+
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&pixels)?;
+
+        Ok(())
+    }
+}
+
+// `WIDTHxHEIGHT`, e.g. 1280x1000
+pub fn parse_size(size: &str) -> Option<(u32, u32)> {
+    let (width, height) = size.split_once('x')?;
+    let width = NonZeroU32::new(width.parse().ok()?)?;
+    let height = NonZeroU32::new(height.parse().ok()?)?;
+
+    Some((width.get(), height.get()))
+}
@@ -0,0 +1,91 @@
+use std::collections::VecDeque;
+
+use glium::backend::Facade;
+use glium::{ Program, Surface };
+
+use crate::cloth::Diagnostics;
+use crate::render::{ self, LineStyle, Vertex, BLACK, IDENTITY };
+
+// graph area in clip space, top left corner of the window
+const LEFT: f32 = -0.95;
+const RIGHT: f32 = -0.35;
+const BOTTOM: f32 = 0.6;
+const TOP: f32 = 0.95;
+
+const KINETIC_COLOR: [f32; 4] = [0.85, 0.1, 0.1, 1.0];
+const POTENTIAL_COLOR: [f32; 4] = [0.1, 0.6, 0.1, 1.0];
+const ELASTIC_COLOR: [f32; 4] = [0.1, 0.2, 0.85, 1.0];
+
+// which value to plot and in what colour
+type Series = (fn(&Diagnostics) -> f32, [f32; 4]);
+
+// scrolling plot of the energy terms over the last `capacity` published states
+pub struct EnergyGraph {
+    history: VecDeque<Diagnostics>,
+    capacity: usize,
+}
+
+impl EnergyGraph {
+    pub fn new(capacity: usize) -> Self {
+        EnergyGraph {
+            history: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, diagnostics: Diagnostics) {
+        if self.history.len() == self.capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(diagnostics);
+    }
+
+    // kinetic red, potential green, elastic blue, total black, all on one shared scale
+    pub fn draw<S: Surface, F: Facade>(&self, target: &mut S, facade: &F, program: &Program) {
+        let frame = [
+            Vertex::at([LEFT, BOTTOM]),
+            Vertex::at([RIGHT, BOTTOM]),
+            Vertex::at([RIGHT, TOP]),
+            Vertex::at([LEFT, TOP]),
+        ];
+        let frame_indices = [0, 1, 1, 2, 2, 3, 3, 0];
+        let style = LineStyle::Solid(BLACK);
+        render::draw_lines(target, facade, program, &frame, &frame_indices, IDENTITY, style);
+
+        if self.history.len() < 2 {
+            return;
+        }
+
+        let series: [Series; 4] = [
+            (|d| d.kinetic, KINETIC_COLOR),
+            (|d| d.potential, POTENTIAL_COLOR),
+            (|d| d.elastic, ELASTIC_COLOR),
+            (Diagnostics::total_energy, BLACK),
+        ];
+
+        let (min, max) = self.history
+            .iter()
+            .flat_map(|d| series.iter().map(move |(value, _)| value(d)))
+            .filter(|value| value.is_finite())
+            .fold((f32::MAX, f32::MIN), |(min, max), value| (min.min(value), max.max(value)));
+        let range = (max - min).max(f32::EPSILON);
+
+        // consecutive samples joined into a line strip
+        let indices: Vec<u32> = (1..self.history.len() as u32).flat_map(|i| [i - 1, i]).collect();
+        let x_step = (RIGHT - LEFT) / ((self.capacity - 1) as f32);
+
+        for (value, color) in series {
+            let vertices: Vec<Vertex> = self.history
+                .iter()
+                .enumerate()
+                .map(|(i, d)| {
+                    let t = ((value(d) - min) / range).clamp(0.0, 1.0);
+                    Vertex::at([LEFT + (i as f32) * x_step, BOTTOM + t * (TOP - BOTTOM)])
+                })
+                .collect();
+
+            let style = LineStyle::Solid(color);
+            render::draw_lines(target, facade, program, &vertices, &indices, IDENTITY, style);
+        }
+    }
+}
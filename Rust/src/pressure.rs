@@ -0,0 +1,147 @@
+use serde::{ Deserialize, Serialize };
+
+use crate::cloth::{ Cloth, Point };
+use crate::material::Material;
+use crate::mesh::Mesh;
+
+// gas trapped inside a closed loop of points. It obeys the ideal gas law at a fixed
+// temperature, so pressure times enclosed area stays at `gas` and squashing the loop pushes
+// back harder. Each side of the loop is pushed outwards by the pressure times its length
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pressure {
+    // point indices going anticlockwise around the loop
+    pub outline: Vec<usize>,
+    pub gas: f32,
+}
+
+impl Pressure {
+    // `pressure` when the loop has its current area, the outline is turned anticlockwise if
+    // it isn't already
+    pub fn new(points: &[Point], mut outline: Vec<usize>, pressure: f32) -> Self {
+        let area = signed_area(points, &outline);
+        if area < 0.0 {
+            outline.reverse();
+        }
+
+        Pressure { outline, gas: pressure * area.abs() }
+    }
+
+    pub fn area(&self, points: &[Point]) -> f32 {
+        signed_area(points, &self.outline)
+    }
+
+    // force on every point, each side's push split evenly between its two ends
+    pub fn forces(&self, points: &[Point]) -> Vec<(f32, f32)> {
+        let mut forces = vec![(0.0, 0.0); points.len()];
+
+        // a loop turned inside out has no sensible pressure, treat it as nearly flat
+        let pressure = self.gas / self.area(points).max(f32::EPSILON);
+
+        for (k, &a) in self.outline.iter().enumerate() {
+            let b = self.outline[(k + 1) % self.outline.len()];
+            let ex = points[b].x - points[a].x;
+            let ey = points[b].y - points[a].y;
+
+            // outward normal scaled by the side's length, the inside is on the left
+            let fx = pressure * ey;
+            let fy = -pressure * ex;
+
+            for index in [a, b] {
+                forces[index].0 += fx / 2.0;
+                forces[index].1 += fy / 2.0;
+            }
+        }
+
+        forces
+    }
+}
+
+// shoelace formula, positive for an anticlockwise loop
+fn signed_area(points: &[Point], outline: &[usize]) -> f32 {
+    let twice_area: f32 = outline
+        .iter()
+        .enumerate()
+        .map(|(k, &a)| {
+            let b = outline[(k + 1) % outline.len()];
+            points[a].x * points[b].y - points[b].x * points[a].y
+        })
+        .sum();
+
+    twice_area / 2.0
+}
+
+// a balloon: a ring of springs inflated by `Pressure`, dropped with its centre at (x, y)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Blob {
+    pub x: f32,
+    pub y: f32,
+    pub radius: f32,
+    pub segments: usize,
+    // at the starting size, in newtons per metre of skin
+    pub pressure: f32,
+    pub stiffness: f32,
+    pub damping: f32,
+}
+
+impl Default for Blob {
+    fn default() -> Self {
+        Blob {
+            x: 0.0,
+            y: 0.0,
+            radius: 5.0,
+            segments: 32,
+            pressure: 1.0,
+            stiffness: 50.0,
+            damping: 0.05,
+        }
+    }
+}
+
+impl Blob {
+    pub fn build(&self) -> Cloth {
+        let skin = Material {
+            warp_stiffness: self.stiffness,
+            warp_damping: self.damping,
+            weft_stiffness: self.stiffness,
+            weft_damping: self.damping,
+            ..Material::default()
+        };
+
+        let mut cloth = Cloth::from_mesh(&Mesh::ring(self.segments, self.radius), &skin);
+        for point in &mut cloth.points {
+            point.x += self.x;
+            point.y += self.y;
+        }
+
+        let outline = (0..cloth.points.len()).collect();
+        cloth.pressure = Some(Pressure::new(&cloth.points, outline, self.pressure));
+        cloth
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn squashed_blob_fills_out_again() {
+        let mut blob = Blob { y: 20.0, ..Blob::default() }.build();
+        blob.g_on = false;
+        let pressure = blob.pressure.clone().unwrap();
+
+        // the gas stretches the skin a little past its rest shape, so compare against a blob
+        // left alone for as long
+        let mut squashed = blob.clone();
+        for point in &mut squashed.points {
+            point.y = 20.0 + (point.y - 20.0) * 0.5;
+        }
+        assert!(pressure.area(&squashed.points) < 0.6 * pressure.area(&blob.points));
+
+        for _ in 0..1000 {
+            blob.simulate_multithreaded(0.01).unwrap();
+            squashed.simulate_multithreaded(0.01).unwrap();
+        }
+        let (area, settled) = (pressure.area(&squashed.points), pressure.area(&blob.points));
+        assert!((area - settled).abs() < 0.05 * settled, "area {} against {}", area, settled);
+    }
+}
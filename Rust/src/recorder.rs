@@ -0,0 +1,359 @@
+use std::fs::{ self, File };
+use std::io::{ self, BufWriter, Write };
+use std::path::Path;
+
+use serde_json::json;
+
+use crate::world::World;
+
+// captures point positions every `every` steps and exports them once recording stops
+pub struct Recorder {
+    pub every: usize,
+    pub dt: f32,
+    steps: usize,
+    indices: Vec<u32>,
+    frames: Vec<Vec<[f32; 2]>>,
+}
+
+impl Recorder {
+    pub fn new(every: usize, dt: f32) -> Self {
+        Recorder {
+            every: every.max(1),
+            dt,
+            steps: 0,
+            indices: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    // call once per simulation step, only every `every`th step is kept
+    pub fn capture(&mut self, world: &World) {
+        let step = self.steps;
+        self.steps += 1;
+
+        if !step.is_multiple_of(self.every) {
+            return;
+        }
+
+        let positions = world.positions();
+
+        // every frame has to share the first frame's topology, start over if it changed
+        if self.frames.first().is_some_and(|first| first.len() != positions.len()) {
+            println!("Cloth topology changed, restarting recording");
+            self.frames.clear();
+            self.steps = 1;
+        }
+
+        if self.frames.is_empty() {
+            self.indices = world.spring_indices();
+        }
+
+        self.frames.push(positions);
+    }
+
+    pub fn num_frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    // seconds of simulated time between two recorded frames
+    fn frame_time(&self) -> f32 {
+        (self.every as f32) * self.dt
+    }
+
+    fn num_points(&self) -> usize {
+        self.frames.first().map_or(0, |frame| frame.len())
+    }
+
+    // write every supported format into `dir`
+    pub fn export(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        self.write_obj_sequence(dir.join("obj"))?;
+        self.write_gltf(dir.join("animation.gltf"))?;
+        self.write_csv(dir.join("tracks.csv"))?;
+        self.write_npy(dir.join("tracks.npy"))?;
+
+        Ok(())
+    }
+
+    // one OBJ per frame, springs as line elements
+    pub fn write_obj_sequence(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        for (frame_index, frame) in self.frames.iter().enumerate() {
+            let path = dir.join(format!("frame_{:05}.obj", frame_index));
+            let mut file = BufWriter::new(File::create(path)?);
+
+            writeln!(file, "# frame {} t={}", frame_index, (frame_index as f32) * self.frame_time())?;
+            for position in frame {
+                writeln!(file, "v {} {} 0", position[0], position[1])?;
+            }
+            for line in self.indices.chunks(2) {
+                // OBJ indices are 1-based
+                writeln!(file, "l {} {}", line[0] + 1, line[1] + 1)?;
+            }
+
+            file.flush()?;
+        }
+
+        Ok(())
+    }
+
+    // a single glTF line mesh with one morph target per frame after the first, and an
+    // animation stepping the target weights so each keyframe shows exactly one frame
+    pub fn write_gltf(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let bin_name = path.with_extension("bin");
+        let bin_uri = bin_name.file_name().unwrap().to_string_lossy().into_owned();
+
+        let num_targets = self.frames.len().saturating_sub(1);
+
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut buffer_views = Vec::new();
+        let mut accessors = Vec::new();
+
+        // appends a tightly packed view, returns its index
+        let mut push_view = |buffer: &mut Vec<u8>, bytes: &[u8], target: Option<u32>| {
+            let offset = buffer.len();
+            buffer.extend_from_slice(bytes);
+            // keep every view 4-byte aligned
+            buffer.resize(buffer.len().next_multiple_of(4), 0);
+
+            let mut view = json!({ "buffer": 0, "byteOffset": offset, "byteLength": bytes.len() });
+            if let Some(target) = target {
+                view["target"] = json!(target);
+            }
+            buffer_views.push(view);
+            buffer_views.len() - 1
+        };
+
+        const ARRAY_BUFFER: u32 = 34962;
+        const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+        const FLOAT: u32 = 5126;
+        const UNSIGNED_INT: u32 = 5125;
+
+        let vec3_accessor = |view: usize, values: &[[f32; 3]]| {
+            let mut min = [f32::MAX; 3];
+            let mut max = [f32::MIN; 3];
+            for value in values {
+                for k in 0..3 {
+                    min[k] = min[k].min(value[k]);
+                    max[k] = max[k].max(value[k]);
+                }
+            }
+
+            json!({
+                "bufferView": view,
+                "componentType": FLOAT,
+                "count": values.len(),
+                "type": "VEC3",
+                "min": min,
+                "max": max,
+            })
+        };
+
+        // base pose
+        let base: Vec<[f32; 3]> = self.frames
+            .first()
+            .map(|frame| frame.iter().map(|p| [p[0], p[1], 0.0]).collect())
+            .unwrap_or_default();
+        let view = push_view(&mut buffer, &f32_bytes(base.iter().flatten()), Some(ARRAY_BUFFER));
+        accessors.push(vec3_accessor(view, &base));
+
+        let view = push_view(&mut buffer, &u32_bytes(&self.indices), Some(ELEMENT_ARRAY_BUFFER));
+        accessors.push(
+            json!({
+                "bufferView": view,
+                "componentType": UNSIGNED_INT,
+                "count": self.indices.len(),
+                "type": "SCALAR",
+            })
+        );
+
+        // one displacement target per later frame
+        let mut targets = Vec::with_capacity(num_targets);
+        for frame in self.frames.iter().skip(1) {
+            let offsets: Vec<[f32; 3]> = frame
+                .iter()
+                .zip(&base)
+                .map(|(p, b)| [p[0] - b[0], p[1] - b[1], 0.0])
+                .collect();
+            let view = push_view(&mut buffer, &f32_bytes(offsets.iter().flatten()), Some(ARRAY_BUFFER));
+            accessors.push(vec3_accessor(view, &offsets));
+            targets.push(json!({ "POSITION": accessors.len() - 1 }));
+        }
+
+        let mut animations = Vec::new();
+        if num_targets > 0 {
+            let times: Vec<f32> = (0..self.frames.len())
+                .map(|i| (i as f32) * self.frame_time())
+                .collect();
+
+            // frame 0 is the base pose with every weight off, frame k turns on target k - 1
+            let mut weights = vec![0.0f32; self.frames.len() * num_targets];
+            for k in 1..self.frames.len() {
+                weights[k * num_targets + (k - 1)] = 1.0;
+            }
+
+            let view = push_view(&mut buffer, &f32_bytes(&times), None);
+            accessors.push(
+                json!({
+                    "bufferView": view,
+                    "componentType": FLOAT,
+                    "count": times.len(),
+                    "type": "SCALAR",
+                    "min": [times[0]],
+                    "max": [times[times.len() - 1]],
+                })
+            );
+            let input = accessors.len() - 1;
+
+            let view = push_view(&mut buffer, &f32_bytes(&weights), None);
+            accessors.push(
+                json!({
+                    "bufferView": view,
+                    "componentType": FLOAT,
+                    "count": weights.len(),
+                    "type": "SCALAR",
+                })
+            );
+            let output = accessors.len() - 1;
+
+            animations.push(
+                json!({
+                    "name": "simulation",
+                    "samplers": [{ "input": input, "output": output, "interpolation": "LINEAR" }],
+                    "channels": [{ "sampler": 0, "target": { "node": 0, "path": "weights" } }],
+                })
+            );
+        }
+
+        let gltf =
+            json!({
+            "asset": { "version": "2.0", "generator": "cloth simulation recorder" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [{ "mesh": 0 }],
+            "meshes": [{
+                "primitives": [{
+                    "attributes": { "POSITION": 0 },
+                    "indices": 1,
+                    "mode": 1,
+                    "targets": targets,
+                }],
+                "weights": vec![0.0f32; num_targets],
+            }],
+            "animations": animations,
+            "buffers": [{ "uri": bin_uri, "byteLength": buffer.len() }],
+            "bufferViews": buffer_views,
+            "accessors": accessors,
+        });
+
+        fs::write(&bin_name, &buffer)?;
+        fs::write(path, serde_json::to_vec_pretty(&gltf)?)
+    }
+
+    // one row per point per frame
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        writeln!(file, "frame,time,point,x,y")?;
+        for (frame_index, frame) in self.frames.iter().enumerate() {
+            let time = (frame_index as f32) * self.frame_time();
+            for (point, position) in frame.iter().enumerate() {
+                writeln!(file, "{},{},{},{},{}", frame_index, time, point, position[0], position[1])?;
+            }
+        }
+
+        file.flush()
+    }
+
+    // float32 array of shape (frames, points, 2), loadable with numpy.load
+    pub fn write_npy(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut header = format!(
+            "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}, 2), }}",
+            self.frames.len(),
+            self.num_points()
+        );
+
+        // magic + version + header length + header must be a multiple of 64, ending in \n
+        let unpadded = 10 + header.len() + 1;
+        header.push_str(&" ".repeat(unpadded.next_multiple_of(64) - unpadded));
+        header.push('\n');
+
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(b"\x93NUMPY\x01\x00")?;
+        file.write_all(&(header.len() as u16).to_le_bytes())?;
+        file.write_all(header.as_bytes())?;
+        file.write_all(&f32_bytes(self.frames.iter().flatten().flatten()))?;
+
+        file.flush()
+    }
+}
+
+fn f32_bytes<'a>(values: impl IntoIterator<Item = &'a f32>) -> Vec<u8> {
+    values
+        .into_iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+fn u32_bytes(values: &[u32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cloth::Cloth;
+
+    fn recording(num_frames: usize) -> Recorder {
+        let world = World::from(Cloth::new(4, 3));
+        let mut recorder = Recorder::new(1, 0.01);
+        for _ in 0..num_frames {
+            recorder.capture(&world);
+        }
+        recorder
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("recorder_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn npy_header_is_padded_to_64_bytes() {
+        let path = temp_path("tracks.npy");
+        recording(3).write_npy(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+
+        let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+        assert!(header.contains("'shape': (3, 12, 2)"));
+        assert!(header.ends_with('\n'));
+        assert_eq!(bytes.len(), 10 + header_len + 3 * 12 * 2 * 4);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn gltf_has_a_weight_per_frame_and_target() {
+        let path = temp_path("animation.gltf");
+        recording(4).write_gltf(&path).unwrap();
+        let gltf: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+
+        let output = gltf["animations"][0]["samplers"][0]["output"].as_u64().unwrap() as usize;
+        assert_eq!(gltf["accessors"][output]["count"], 4 * 3);
+        assert_eq!(gltf["meshes"][0]["primitives"][0]["targets"].as_array().unwrap().len(), 3);
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(path.with_extension("bin")).unwrap();
+    }
+}
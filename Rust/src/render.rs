@@ -0,0 +1,183 @@
+use glium::{ IndexBuffer, Program, Surface, VertexBuffer };
+use glium::backend::Facade;
+use glium::index::{ IndexBufferAny, PrimitiveType };
+use std::fs;
+use std::io::Read;
+
+use crate::rigid::RigidBody;
+use crate::world::ClothLayout;
+
+#[derive(Copy, Clone)]
+pub struct Vertex {
+    pub position: [f32; 2],
+    pub heat: f32,
+}
+
+implement_vertex!(Vertex, position, heat);
+
+impl Vertex {
+    pub fn at(position: [f32; 2]) -> Self {
+        Vertex { position, heat: 0.0 }
+    }
+}
+
+// how lines are coloured, a heatmap reads each vertex's heat
+#[derive(Copy, Clone)]
+pub enum LineStyle {
+    Solid([f32; 4]),
+    Heatmap,
+}
+
+fn read_shader_src(path: &str) -> &'static str {
+    let mut src = String::new();
+    let mut file = fs::File::open(path).expect("Failed to open shader file");
+    file.read_to_string(&mut src).expect("Failed to read shader file");
+
+    Box::leak(src.into_boxed_str())
+}
+
+pub fn build_program<F: Facade>(facade: &F) -> Program {
+    let vertex_shader_src = read_shader_src("src/shaders/vertex.glsl");
+    let fragment_shader_src = read_shader_src("src/shaders/fragment.glsl");
+
+    Program::from_source(facade, vertex_shader_src, fragment_shader_src, None).unwrap()
+}
+
+// spring indices ready for upload, narrowed to u16 when every vertex fits
+#[derive(Debug, PartialEq)]
+pub enum IndexData {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+// u16 indices are cheaper to upload and draw, so only fall back to u32 when the grid needs it
+pub fn index_data(indices: &[u32], num_vertices: usize) -> IndexData {
+    if num_vertices <= (u16::MAX as usize) {
+        IndexData::U16(indices.iter().map(|&i| i as u16).collect())
+    } else {
+        IndexData::U32(indices.to_vec())
+    }
+}
+
+pub fn build_index_buffer<F: Facade>(
+    facade: &F,
+    indices: &[u32],
+    num_vertices: usize
+) -> IndexBufferAny {
+    match index_data(indices, num_vertices) {
+        IndexData::U16(indices) => IndexBuffer::new(facade, PrimitiveType::LinesList, &indices).unwrap().into(),
+        IndexData::U32(indices) => IndexBuffer::new(facade, PrimitiveType::LinesList, &indices).unwrap().into(),
+    }
+}
+
+pub const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+pub const BODY_COLOR: [f32; 4] = [0.2, 0.3, 0.8, 1.0];
+
+pub const IDENTITY: [[f32; 4]; 4] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0f32],
+];
+
+// world to clip space, aspect ratio is height / width
+pub fn view_matrix(aspect_ratio: f32) -> [[f32; 4]; 4] {
+    [
+        [0.03 * aspect_ratio, 0.0, 0.0, 0.0],
+        [0.0, 0.03, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0f32],
+    ]
+}
+
+// every spring of every cloth as a line between its two points, each cloth in its own colour
+// or all of them on the heatmap. Shared by the window and offscreen paths
+pub fn draw_springs<S: Surface, F: Facade>(
+    target: &mut S,
+    facade: &F,
+    program: &Program,
+    positions: impl Iterator<Item = [f32; 2]>,
+    heat: Option<&[f32]>,
+    layout: &[ClothLayout],
+    aspect_ratio: f32
+) {
+    let vertices: Vec<Vertex> = match heat {
+        Some(heat) =>
+            positions
+                .zip(heat)
+                .map(|(position, &heat)| Vertex { position, heat })
+                .collect(),
+        None => positions.map(Vertex::at).collect(),
+    };
+
+    for cloth in layout {
+        let style = if heat.is_some() { LineStyle::Heatmap } else { LineStyle::Solid(cloth.color) };
+        let vertices = &vertices[cloth.first_point..cloth.first_point + cloth.num_points];
+        draw_lines(target, facade, program, vertices, &cloth.indices, view_matrix(aspect_ratio), style);
+    }
+}
+
+// outlines of the rigid bodies, in world space like the springs
+pub fn draw_bodies<S: Surface, F: Facade>(
+    target: &mut S,
+    facade: &F,
+    program: &Program,
+    bodies: &[RigidBody],
+    aspect_ratio: f32
+) {
+    for body in bodies {
+        let vertices: Vec<Vertex> = body.outline().into_iter().map(Vertex::at).collect();
+        let n = vertices.len() as u32;
+        let indices: Vec<u32> = (0..n).flat_map(|i| [i, (i + 1) % n]).collect();
+
+        let style = LineStyle::Solid(BODY_COLOR);
+        draw_lines(target, facade, program, &vertices, &indices, view_matrix(aspect_ratio), style);
+    }
+}
+
+pub fn draw_lines<S: Surface, F: Facade>(
+    target: &mut S,
+    facade: &F,
+    program: &Program,
+    vertices: &[Vertex],
+    indices: &[u32],
+    matrix: [[f32; 4]; 4],
+    style: LineStyle
+) {
+    let (color, use_heatmap) = match style {
+        LineStyle::Solid(color) => (color, false),
+        LineStyle::Heatmap => (BLACK, true),
+    };
+
+    // create vertex and index buffer
+    let vertex_buffer = VertexBuffer::new(facade, vertices).unwrap();
+    let index_buffer = build_index_buffer(facade, indices, vertices.len());
+
+    let uniforms =
+        uniform! {
+        matrix: matrix,
+        line_color: color,
+        use_heatmap: use_heatmap,
+    };
+
+    target
+        .draw(&vertex_buffer, &index_buffer, program, &uniforms, &Default::default())
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cloth::Cloth;
+
+    #[test]
+    fn large_cloth_indices_are_in_range() {
+        let cloth = Cloth::new(300, 300);
+        let indices = cloth.spring_indices();
+
+        assert_eq!(cloth.points.len(), 90_000);
+        assert!(indices.iter().all(|&i| i < 90_000));
+        assert_eq!(index_data(&indices, cloth.points.len()), IndexData::U32(indices.clone()));
+        assert!(matches!(index_data(&indices[..2], u16::MAX as usize), IndexData::U16(_)));
+    }
+}
@@ -0,0 +1,163 @@
+use serde::{ Deserialize, Serialize };
+
+use crate::cloth::Point;
+
+// everything falls onto this
+pub const FLOOR_Y: f32 = -32.0;
+
+// how much normal velocity survives a contact, 0 is perfectly inelastic
+const RESTITUTION: f32 = 0.3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Shape {
+    Circle {
+        radius: f32,
+    },
+    // axis aligned, it never rotates
+    Box {
+        half_width: f32,
+        half_height: f32,
+    },
+}
+
+// a solid that falls under gravity and trades momentum with the cloth points it touches.
+// Contacts are point-vs-shape only, so a body smaller than the spring rest length can slip
+// through the gaps between points
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RigidBody {
+    pub shape: Shape,
+    pub x: f32,
+    pub y: f32,
+    pub vx: f32,
+    pub vy: f32,
+    pub mass: f32,
+}
+
+impl RigidBody {
+    pub fn circle(x: f32, y: f32, radius: f32, mass: f32) -> Self {
+        RigidBody { shape: Shape::Circle { radius }, x, y, vx: 0.0, vy: 0.0, mass }
+    }
+
+    pub fn rect(x: f32, y: f32, width: f32, height: f32, mass: f32) -> Self {
+        let shape = Shape::Box { half_width: width / 2.0, half_height: height / 2.0 };
+        RigidBody { shape, x, y, vx: 0.0, vy: 0.0, mass }
+    }
+
+    // distance from the centre to the lowest point of the shape
+    fn half_height(&self) -> f32 {
+        match self.shape {
+            Shape::Circle { radius } => radius,
+            Shape::Box { half_height, .. } => half_height,
+        }
+    }
+
+    // outward normal and depth if (x, y) is inside the shape
+    fn penetration(&self, x: f32, y: f32) -> Option<((f32, f32), f32)> {
+        let dx = x - self.x;
+        let dy = y - self.y;
+
+        match self.shape {
+            Shape::Circle { radius } => {
+                let distance = (dx * dx + dy * dy).sqrt();
+                if distance >= radius {
+                    return None;
+                }
+
+                // a point dead in the centre gets pushed straight up
+                let normal = if distance > 0.0 { (dx / distance, dy / distance) } else { (0.0, 1.0) };
+                Some((normal, radius - distance))
+            }
+            Shape::Box { half_width, half_height } => {
+                let depth_x = half_width - dx.abs();
+                let depth_y = half_height - dy.abs();
+                if depth_x <= 0.0 || depth_y <= 0.0 {
+                    return None;
+                }
+
+                // out through the nearest side
+                if depth_x < depth_y {
+                    Some(((dx.signum(), 0.0), depth_x))
+                } else {
+                    Some(((0.0, dy.signum()), depth_y))
+                }
+            }
+        }
+    }
+
+    // outline for drawing, as a closed loop
+    pub fn outline(&self) -> Vec<[f32; 2]> {
+        match self.shape {
+            Shape::Circle { radius } => {
+                const SEGMENTS: usize = 24;
+                (0..SEGMENTS)
+                    .map(|i| {
+                        let angle = ((i as f32) / (SEGMENTS as f32)) * std::f32::consts::TAU;
+                        [self.x + radius * angle.cos(), self.y + radius * angle.sin()]
+                    })
+                    .collect()
+            }
+            Shape::Box { half_width, half_height } => {
+                vec![
+                    [self.x - half_width, self.y - half_height],
+                    [self.x + half_width, self.y - half_height],
+                    [self.x + half_width, self.y + half_height],
+                    [self.x - half_width, self.y + half_height]
+                ]
+            }
+        }
+    }
+
+    pub fn kinetic_energy(&self) -> f32 {
+        0.5 * self.mass * (self.vx * self.vx + self.vy * self.vy)
+    }
+}
+
+// move the bodies under gravity and bounce them off the floor
+pub fn integrate(bodies: &mut [RigidBody], dt: f32, gravity: f32) {
+    for body in bodies.iter_mut() {
+        body.vy -= gravity * dt;
+        body.x += body.vx * dt;
+        body.y += body.vy * dt;
+
+        let lowest = FLOOR_Y + body.half_height();
+        if body.y < lowest {
+            body.y = lowest;
+            body.vy = -body.vy * RESTITUTION;
+        }
+    }
+}
+
+// resolve the bodies' contacts with one cloth. Each contact is an impulse along the normal that
+// conserves the pair's momentum, fixed points act as if infinitely heavy
+pub fn collide(bodies: &mut [RigidBody], points: &mut [Point]) {
+    for body in bodies.iter_mut() {
+        for point in points.iter_mut() {
+            let Some(((nx, ny), depth)) = body.penetration(point.x, point.y) else {
+                continue;
+            };
+
+            let inv_point_mass = if point.fixed { 0.0 } else { 1.0 / point.mass };
+            let inv_body_mass = 1.0 / body.mass;
+            let inv_mass_sum = inv_point_mass + inv_body_mass;
+
+            // separate them, the lighter one moves further
+            let point_share = inv_point_mass / inv_mass_sum;
+            point.x += nx * depth * point_share;
+            point.y += ny * depth * point_share;
+            body.x -= nx * depth * (1.0 - point_share);
+            body.y -= ny * depth * (1.0 - point_share);
+
+            // only push apart if they are still closing
+            let closing = (point.vx - body.vx) * nx + (point.vy - body.vy) * ny;
+            if closing >= 0.0 {
+                continue;
+            }
+
+            let impulse = (-(1.0 + RESTITUTION) * closing) / inv_mass_sum;
+            point.vx += impulse * nx * inv_point_mass;
+            point.vy += impulse * ny * inv_point_mass;
+            body.vx -= impulse * nx * inv_body_mass;
+            body.vy -= impulse * ny * inv_body_mass;
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use crate::cloth::{ Cloth, Spring };
+use crate::material::Material;
+use crate::mesh::Mesh;
+
+// which ends of a rope are nailed in place
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RopeEnds {
+    Free,
+    // a pendulum
+    #[default]
+    Start,
+    // a cable slung between two posts
+    Both,
+}
+
+impl RopeEnds {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "none" | "free" => Some(RopeEnds::Free),
+            "start" => Some(RopeEnds::Start),
+            "both" => Some(RopeEnds::Both),
+            _ => None,
+        }
+    }
+}
+
+// a one dimensional cloth: a chain of springs laid out left to right. Bending stiffness adds
+// springs between every other point, an inextensible rope behaves like a chain of rigid links
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rope {
+    pub segments: usize,
+    pub segment_length: f32,
+    pub ends: RopeEnds,
+    // 0 for a rope that folds freely
+    pub bending_stiffness: f32,
+    pub inextensible: bool,
+    // how much shorter than the rope the gap between its ends starts out, as a fraction of
+    // its length, so a rope pinned at both ends has something to sag with
+    pub slack: f32,
+    pub material: Material,
+}
+
+impl Default for Rope {
+    fn default() -> Self {
+        Rope {
+            segments: 40,
+            segment_length: 1.0,
+            ends: RopeEnds::default(),
+            bending_stiffness: 0.0,
+            inextensible: false,
+            slack: 0.0,
+            material: Material::default(),
+        }
+    }
+}
+
+impl Rope {
+    pub fn build(&self) -> Cloth {
+        let mut cloth = Cloth::from_mesh(&Mesh::rope(self.segments, self.segment_length), &self.material);
+
+        // bunch the points up without touching the rest lengths
+        for point in &mut cloth.points {
+            point.x *= 1.0 - self.slack;
+        }
+
+        if self.bending_stiffness > 0.0 {
+            for i in 0..self.segments.saturating_sub(1) {
+                let mut spring = Spring::new(i, i + 2, 2.0 * self.segment_length);
+                spring.spring_coeff = self.bending_stiffness;
+                spring.damp_coeff = self.material.weft_damping;
+                spring.bending = true;
+                cloth.springs.push(spring);
+            }
+        }
+
+        let last = cloth.points.len() - 1;
+        match self.ends {
+            RopeEnds::Free => (),
+            RopeEnds::Start => cloth.points[0].fixed = true,
+            RopeEnds::Both => {
+                cloth.points[0].fixed = true;
+                cloth.points[last].fixed = true;
+            }
+        }
+
+        cloth.inextensible = self.inextensible;
+        cloth
+    }
+}
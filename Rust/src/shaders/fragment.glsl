@@ -1,8 +1,19 @@
 #version 140
 
+in float v_heat;
+
+uniform vec4 line_color;
+uniform bool use_heatmap;
+
 out vec4 color;
 
 void main()
 {
-    color = vec4(0.0, 0.0, 0.0, 1.0);
+    if (use_heatmap) {
+        // blue for 0 through green to red for 1
+        float t = clamp(v_heat, 0.0, 1.0);
+        color = vec4(smoothstep(0.5, 1.0, t), 1.0 - abs(2.0 * t - 1.0), 1.0 - smoothstep(0.0, 0.5, t), 1.0);
+    } else {
+        color = line_color;
+    }
 }
\ No newline at end of file
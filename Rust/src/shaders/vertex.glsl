@@ -1,9 +1,13 @@
 #version 140
 
 in vec2 position;
+in float heat;
+
+out float v_heat;
 
 uniform mat4 matrix;
 
 void main() {
+    v_heat = heat;
     gl_Position = matrix * vec4(position, 0.0, 1.0);
 }
\ No newline at end of file
@@ -0,0 +1,210 @@
+use rayon::prelude::*;
+use serde::{ Deserialize, Serialize };
+
+use crate::cloth::{ Cloth, Point };
+use crate::material::Material;
+use crate::matrix::polar_rotation;
+use crate::mesh::Mesh;
+
+// points that remember the shape they were made in
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Group {
+    pub indices: Vec<usize>,
+    // each point's rest position relative to the group's rest centre of mass
+    pub rest: Vec<[f32; 2]>,
+}
+
+// meshless shape matching (Müller et al. 2005). After every step each group finds the rotation
+// and translation that best fit its rest shape onto where its points are now, and pulls the
+// points a `stiffness` fraction of the way towards that fit. Points in several overlapping
+// groups move by the average pull, which lets a body bend while each group stays nearly rigid.
+// Runs on top of the springs, if there are any
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ShapeMatching {
+    pub groups: Vec<Group>,
+    // 0 for no effect, 1 snaps every group straight back into shape
+    pub stiffness: f32,
+}
+
+impl ShapeMatching {
+    // the groups' rest shapes are taken from the points as they are now
+    pub fn new(points: &[Point], groups: Vec<Vec<usize>>, stiffness: f32) -> Self {
+        let groups = groups
+            .into_iter()
+            .map(|indices| {
+                let (cx, cy) = centre_of_mass(points, &indices);
+                let rest = indices
+                    .iter()
+                    .map(|&index| [points[index].x - cx, points[index].y - cy])
+                    .collect();
+
+                Group { indices, rest }
+            })
+            .collect();
+
+        ShapeMatching { groups, stiffness }
+    }
+
+    // move the points towards their goal positions and give them the velocity that took, like
+    // `Cloth::enforce_lengths`. Fixed points stay put
+    pub fn apply(&self, points: &mut [Point], dt: f32) {
+        let goals: Vec<Vec<[f32; 2]>> = self.groups
+            .par_iter()
+            .map(|group| group.goals(points))
+            .collect();
+
+        let mut pulls = vec![(0.0, 0.0, 0); points.len()];
+        for (group, goals) in self.groups.iter().zip(goals) {
+            for (&index, [gx, gy]) in group.indices.iter().zip(goals) {
+                let pull = &mut pulls[index];
+                pull.0 += gx - points[index].x;
+                pull.1 += gy - points[index].y;
+                pull.2 += 1;
+            }
+        }
+
+        for (point, (dx, dy, count)) in points.iter_mut().zip(pulls) {
+            if point.fixed || count == 0 {
+                continue;
+            }
+
+            let dx = (self.stiffness * dx) / (count as f32);
+            let dy = (self.stiffness * dy) / (count as f32);
+            point.x += dx;
+            point.y += dy;
+            point.vx += dx / dt;
+            point.vy += dy / dt;
+        }
+    }
+}
+
+impl Group {
+    // the rest shape rotated and moved onto the points, the rotation coming out of the polar
+    // decomposition of the covariance matrix A = sum m (x - c) rest^T
+    fn goals(&self, points: &[Point]) -> Vec<[f32; 2]> {
+        let (cx, cy) = centre_of_mass(points, &self.indices);
+
+        let mut covariance = [[0.0; 2]; 2];
+        for (&index, [rx, ry]) in self.indices.iter().zip(&self.rest) {
+            let point = &points[index];
+            let (px, py) = (point.x - cx, point.y - cy);
+
+            covariance[0][0] += point.mass * px * rx;
+            covariance[0][1] += point.mass * px * ry;
+            covariance[1][0] += point.mass * py * rx;
+            covariance[1][1] += point.mass * py * ry;
+        }
+
+        let [[r00, r01], [r10, r11]] = polar_rotation(&covariance);
+
+        self.rest
+            .iter()
+            .map(|[rx, ry]| [cx + r00 * rx + r01 * ry, cy + r10 * rx + r11 * ry])
+            .collect()
+    }
+}
+
+fn centre_of_mass(points: &[Point], indices: &[usize]) -> (f32, f32) {
+    let (mass, mx, my) = indices
+        .iter()
+        .map(|&index| &points[index])
+        .fold((0.0, 0.0, 0.0), |(mass, mx, my), point| {
+            (mass + point.mass, mx + point.mass * point.x, my + point.mass * point.y)
+        });
+
+    (mx / mass, my / mass)
+}
+
+// a block of jelly: a soft spring grid held in shape by overlapping `cluster` x `cluster`
+// groups, dropped with its centre at (x, y). Bigger clusters make it stiffer, a cluster as big
+// as the block makes it a single nearly rigid group
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Jelly {
+    pub x: f32,
+    pub y: f32,
+    pub rows: usize,
+    pub cols: usize,
+    pub spacing: f32,
+    pub cluster: usize,
+    pub stiffness: f32,
+    pub material: Material,
+}
+
+impl Default for Jelly {
+    fn default() -> Self {
+        Jelly {
+            x: 0.0,
+            y: 0.0,
+            rows: 8,
+            cols: 8,
+            spacing: 1.0,
+            cluster: 4,
+            stiffness: 0.5,
+            material: Material::RUBBER,
+        }
+    }
+}
+
+impl Jelly {
+    pub fn build(&self) -> Cloth {
+        let mut cloth = Cloth::from_mesh(&Mesh::grid(self.rows, self.cols, self.spacing), &self.material);
+
+        let width = ((self.cols - 1) as f32) * self.spacing;
+        let height = ((self.rows - 1) as f32) * self.spacing;
+        for point in &mut cloth.points {
+            point.x += self.x - width / 2.0;
+            point.y += self.y - height / 2.0;
+        }
+
+        // one group starting at every grid point that leaves room for a whole cluster
+        let rows = self.cluster.clamp(1, self.rows);
+        let cols = self.cluster.clamp(1, self.cols);
+        let mut groups = Vec::new();
+        for i in 0..=self.rows - rows {
+            for j in 0..=self.cols - cols {
+                let group = (i..i + rows)
+                    .flat_map(|row| (j..j + cols).map(move |col| row * self.cols + col))
+                    .collect();
+                groups.push(group);
+            }
+        }
+
+        cloth.shape_matching = Some(ShapeMatching::new(&cloth.points, groups, self.stiffness));
+        cloth
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every point's distance to every other, which a rotation leaves alone
+    fn distances(points: &[Point]) -> Vec<f32> {
+        points
+            .iter()
+            .flat_map(|a| points.iter().map(move |b| ((b.x - a.x).powi(2) + (b.y - a.y).powi(2)).sqrt()))
+            .collect()
+    }
+
+    #[test]
+    fn turned_and_squashed_jelly_springs_back() {
+        let mut jelly = Jelly { y: 20.0, ..Jelly::default() }.build();
+        jelly.g_on = false;
+        let rest = distances(&jelly.points);
+
+        // squashed, then turned about the middle so the rest shape has to be found at an angle
+        let (sin, cos) = 1.2f32.sin_cos();
+        for point in &mut jelly.points {
+            let (x, y) = (point.x, (point.y - 20.0) * 0.6);
+            point.x = cos * x - sin * y;
+            point.y = 20.0 + sin * x + cos * y;
+        }
+
+        for _ in 0..300 {
+            jelly.simulate_multithreaded(0.01).unwrap();
+        }
+        for (distance, rest) in distances(&jelly.points).iter().zip(&rest) {
+            assert!((distance - rest).abs() < 0.05 * rest.max(1.0));
+        }
+    }
+}
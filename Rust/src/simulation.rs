@@ -0,0 +1,560 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::mpsc::{ self, Receiver, Sender };
+use std::thread::{ self, JoinHandle };
+use std::time::{ Duration, Instant };
+
+use triple_buffer::{ triple_buffer, Input, Output };
+
+use crate::cloth::{ Cloth, Damping, Diagnostics, SimError, StepStats };
+use crate::aero::Aerodynamics;
+use crate::fem::ForceModel;
+use crate::heatmap::Heatmap;
+use crate::material::Material;
+use crate::recorder::Recorder;
+use crate::rigid::RigidBody;
+use crate::timestep::FixedTimestep;
+use crate::world::{ ClothLayout, World };
+
+// input from the window, applied by the simulation thread between steps
+pub enum Command {
+    ToggleGravity,
+    SetExternalForce {
+        cloth: usize,
+        point: usize,
+        magnitude: f32,
+    },
+    SetTimeScale(f32),
+    TogglePause,
+    Step,
+    Rewind,
+    ToggleAdaptive,
+    CycleHeatmap,
+    CycleMaterial,
+    ToggleDamping,
+    ToggleForceModel,
+    ToggleWind,
+    ToggleClothCollisions,
+    DropBody(RigidBody),
+    AddCloth(Box<Cloth>),
+    // hold a point under the mouse until `Release`, moving it with `DragTo`
+    Grab {
+        cloth: usize,
+        point: usize,
+        x: f32,
+        y: f32,
+    },
+    DragTo {
+        x: f32,
+        y: f32,
+    },
+    Release,
+    // blow hot air at this spot every step, `None` switches it off
+    HeatGun(Option<[f32; 2]>),
+    Save(PathBuf),
+    Load(PathBuf),
+    ToggleRecording {
+        dir: PathBuf,
+        every: usize,
+    },
+    Quit,
+}
+
+// everything the renderer needs to draw a frame. The state is shared so a snapshot can be
+// republished with a new alpha on every pass without walking the cloths again
+#[derive(Clone, Default)]
+pub struct Snapshot {
+    pub prev_positions: Arc<Vec<[f32; 2]>>,
+    pub positions: Arc<Vec<[f32; 2]>>,
+    pub alpha: f32,
+    // steps taken before this state, including single steps and rewinds
+    pub step: u64,
+    pub layout: Arc<Vec<ClothLayout>>,
+    pub diagnostics: Diagnostics,
+    pub heat: Option<Arc<Vec<f32>>>,
+    pub bodies: Arc<Vec<RigidBody>>,
+}
+
+impl Snapshot {
+    fn new(
+        world: &World,
+        prev_positions: Vec<[f32; 2]>,
+        alpha: f32,
+        step: u64,
+        layout: &Arc<Vec<ClothLayout>>,
+        heatmap: Heatmap
+    ) -> Self {
+        Snapshot {
+            prev_positions: Arc::new(prev_positions),
+            positions: Arc::new(world.positions()),
+            alpha,
+            step,
+            layout: Arc::clone(layout),
+            diagnostics: world.diagnostics(),
+            heat: heatmap.values(world).map(Arc::new),
+            bodies: Arc::new(world.bodies.clone()),
+        }
+    }
+
+    // positions blended between the last two simulation states
+    pub fn interpolated(&self) -> impl Iterator<Item = [f32; 2]> + '_ {
+        self.prev_positions
+            .iter()
+            .zip(self.positions.iter())
+            .map(move |(prev, curr)| [
+                prev[0] + (curr[0] - prev[0]) * self.alpha,
+                prev[1] + (curr[1] - prev[1]) * self.alpha,
+            ])
+    }
+}
+
+// timings collected over the thread's lifetime, one entry per fixed step
+#[derive(Default)]
+pub struct RunStats {
+    // wall time in milliseconds
+    pub simulation_times: Vec<f32>,
+    // dt and substeps actually used, these differ from the fixed dt under adaptive stepping
+    pub steps: Vec<StepStats>,
+}
+
+impl RunStats {
+    pub fn record(&mut self, sim_time_ms: f32, step: Option<StepStats>) {
+        self.simulation_times.push(sim_time_ms);
+        self.steps.extend(step);
+    }
+
+    pub fn print_summary(&self) {
+        if !self.simulation_times.is_empty() {
+            let total_sims = self.simulation_times.len();
+            let avg_time: f32 = self.simulation_times.iter().sum::<f32>() / (total_sims as f32);
+            println!("Average Simulation Time: {} ms", avg_time);
+        }
+
+        if !self.steps.is_empty() {
+            let total_steps = self.steps.len() as f32;
+            let substeps: usize = self.steps.iter().map(|step| step.substeps).sum();
+            // summed in f64, thousands of near-identical f32 dts drift visibly otherwise
+            let avg_dt = self.steps.iter().map(|step| step.dt as f64).sum::<f64>() / (total_steps as f64);
+            let min_dt = self.steps.iter().map(|step| step.dt).fold(f32::MAX, f32::min);
+
+            println!("Average dt: {} s (min {} s)", avg_dt, min_dt);
+            println!("Average Substeps: {}", (substeps as f32) / total_steps);
+        }
+    }
+}
+
+pub struct SimulationThread {
+    pub commands: Sender<Command>,
+    pub snapshots: Output<Snapshot>,
+    handle: Option<JoinHandle<RunStats>>,
+}
+
+impl SimulationThread {
+    pub fn spawn(world: World, timestep: FixedTimestep) -> Self {
+        let layout = Arc::new(world.layout());
+        let initial = Snapshot::new(&world, world.positions(), 0.0, 0, &layout, Heatmap::Off);
+
+        let (input, output) = triple_buffer(&initial);
+        let (sender, receiver) = mpsc::channel();
+
+        let handle = thread::Builder
+            ::new()
+            .name("simulation".to_string())
+            .spawn(move || run(world, timestep, layout, receiver, input))
+            .expect("failed to spawn simulation thread");
+
+        SimulationThread {
+            commands: sender,
+            snapshots: output,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn send(&self, command: Command) {
+        // the thread only goes away after Quit, so a failed send has nowhere useful to go
+        let _ = self.commands.send(command);
+    }
+
+    // stop the thread and return its timings
+    pub fn stop(&mut self) -> RunStats {
+        self.send(Command::Quit);
+
+        self.handle
+            .take()
+            .map(|handle| handle.join().expect("simulation thread panicked"))
+            .unwrap_or_default()
+    }
+}
+
+// how many past steps can be rewound through
+const HISTORY_LEN: usize = 256;
+
+// reach of the heat gun and how fast it heats the middle of that, in degrees per second
+const HEAT_GUN_RADIUS: f32 = 4.0;
+const HEAT_GUN_RATE: f32 = 200.0;
+
+// a diverging step is retried with dt halved at most this many times before giving up
+const MAX_HALVINGS: u32 = 4;
+
+// cap on substeps per fixed step when stepping adaptively
+const MAX_SUBSTEPS: usize = 64;
+
+// a point held by the mouse. It is pinned while held and steered onto `target` each step,
+// its springs drag the rest of the cloth along so a rope can be swung by its end
+struct Grab {
+    cloth: usize,
+    point: usize,
+    target: [f32; 2],
+    // whether to leave it pinned on release
+    was_fixed: bool,
+}
+
+// simulation state owned by the thread
+struct Simulation {
+    world: World,
+    prev_positions: Vec<[f32; 2]>,
+    history: VecDeque<World>,
+    recorder: Option<Recorder>,
+    adaptive: bool,
+    grab: Option<Grab>,
+    heat_gun: Option<[f32; 2]>,
+    stats: RunStats,
+}
+
+impl Simulation {
+    fn step(&mut self, dt: f32) -> Result<(), SimError> {
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.world.clone());
+
+        self.drag(dt);
+        if let Some([x, y]) = self.heat_gun {
+            self.world.heat(x, y, HEAT_GUN_RADIUS, HEAT_GUN_RATE, dt);
+        }
+
+        self.prev_positions = self.world.positions();
+
+        let sim_start = Instant::now();
+        let result = if self.adaptive {
+            self.world.advance(dt, MAX_SUBSTEPS)
+        } else {
+            self.world.step_with_recovery(dt, MAX_HALVINGS)
+        };
+        let sim_end = Instant::now();
+        let sim_time = sim_end.duration_since(sim_start).as_micros();
+        self.stats.record((sim_time as f32) / 1000.0, result.ok()); // convert to millis
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.capture(&self.world);
+        }
+
+        result.map(|_| ())
+    }
+
+    // move the held point onto the mouse over this step, with the velocity that takes. A
+    // free point keeps that velocity when let go, so it can be thrown
+    fn drag(&mut self, dt: f32) {
+        let Some(grab) = &self.grab else {
+            return;
+        };
+
+        // the point may be gone if a smaller snapshot was loaded since it was grabbed
+        let Some(point) = self.world.cloths
+            .get_mut(grab.cloth)
+            .and_then(|cloth| cloth.points.get_mut(grab.point)) else {
+            return;
+        };
+
+        point.fixed = true;
+        point.vx = (grab.target[0] - point.x) / dt;
+        point.vy = (grab.target[1] - point.y) / dt;
+        point.x = grab.target[0];
+        point.y = grab.target[1];
+    }
+
+    fn release(&mut self) {
+        let Some(grab) = self.grab.take() else {
+            return;
+        };
+
+        let point = self.world.cloths
+            .get_mut(grab.cloth)
+            .and_then(|cloth| cloth.points.get_mut(grab.point));
+        if let Some(point) = point {
+            point.fixed = grab.was_fixed;
+            if grab.was_fixed {
+                point.vx = 0.0;
+                point.vy = 0.0;
+            }
+        }
+    }
+
+    // replace the world outright, the old history no longer applies to it
+    fn load(&mut self, world: World) {
+        self.world = world;
+        self.prev_positions = self.world.positions();
+        self.history.clear();
+        self.grab = None;
+    }
+
+    // the point counts change, so interpolating from the old positions no longer lines up
+    // dropped in to land on what's already there, so cloths collide from here on (C turns it
+    // back off)
+    fn add_cloth(&mut self, cloth: Cloth) {
+        self.world.cloths.push(cloth);
+        self.world.collide_cloths = true;
+        self.prev_positions = self.world.positions();
+    }
+
+    // go back one step, returns false once the history runs out
+    fn rewind(&mut self) -> bool {
+        match self.history.pop_back() {
+            Some(world) => {
+                self.world = world;
+                self.prev_positions = self.world.positions();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn run(
+    world: World,
+    mut timestep: FixedTimestep,
+    mut layout: Arc<Vec<ClothLayout>>,
+    commands: Receiver<Command>,
+    mut snapshots: Input<Snapshot>
+) -> RunStats {
+    let mut simulation = Simulation {
+        prev_positions: world.positions(),
+        world,
+        history: VecDeque::with_capacity(HISTORY_LEN),
+        recorder: None,
+        adaptive: false,
+        grab: None,
+        heat_gun: None,
+        stats: RunStats::default(),
+    };
+    let mut paused = false;
+    let mut heatmap = Heatmap::Off;
+    let mut material = Material::PRESETS.len() - 1;
+    let mut last_time = Instant::now();
+    let mut latest = Snapshot::new(&simulation.world, simulation.prev_positions.clone(), 0.0, 0, &layout, heatmap);
+    let mut step = 0;
+
+    loop {
+        let mut steps = 0;
+        let mut changed = false;
+
+        for command in commands.try_iter() {
+            // anything else can show up in the next snapshot
+            changed |= !matches!(
+                command,
+                Command::SetTimeScale(_) | Command::ToggleAdaptive | Command::Save(_) | Command::ToggleRecording { .. }
+            );
+
+            match command {
+                Command::ToggleGravity => {
+                    let g_on = simulation.world.cloths.first().is_some_and(|cloth| cloth.g_on);
+                    simulation.world.set_gravity(!g_on);
+                }
+                Command::SetExternalForce { cloth, point, magnitude } => {
+                    // the point may be gone if a smaller snapshot was loaded since it was picked
+                    let point = simulation.world.cloths
+                        .get_mut(cloth)
+                        .and_then(|cloth| cloth.points.get_mut(point));
+                    if let Some(point) = point {
+                        point.ext_m = magnitude;
+                    }
+                }
+                Command::SetTimeScale(time_scale) => {
+                    timestep.time_scale = time_scale;
+                }
+                Command::TogglePause => {
+                    paused = !paused;
+                    println!("{}", if paused { "Paused" } else { "Resumed" });
+                }
+                Command::Step => {
+                    paused = true;
+                    if let Err(e) = simulation.step(timestep.dt) {
+                        println!("Simulation diverged: {}", e);
+                    }
+                    steps += 1;
+                }
+                Command::Rewind => {
+                    paused = true;
+                    if simulation.rewind() {
+                        // rewinding past an added cloth takes it away again
+                        if layout.len() != simulation.world.cloths.len() {
+                            layout = Arc::new(simulation.world.layout());
+                        }
+                        steps += 1;
+                    } else {
+                        println!("Rewind history exhausted");
+                    }
+                }
+                Command::CycleHeatmap => {
+                    heatmap = heatmap.next();
+                    println!("Heatmap: {:?}", heatmap);
+                }
+                Command::CycleMaterial => {
+                    material = (material + 1) % Material::PRESETS.len();
+                    let (name, preset) = Material::PRESETS[material];
+                    for cloth in &mut simulation.world.cloths {
+                        cloth.apply_material(&preset);
+                    }
+                    println!("Material: {}", name);
+                }
+                Command::ToggleDamping => {
+                    let legacy = simulation.world.cloths.first().is_some_and(|cloth| cloth.damping == Damping::Legacy);
+                    let damping = if legacy { Damping::Relative } else { Damping::Legacy };
+                    for cloth in &mut simulation.world.cloths {
+                        cloth.damping = damping;
+                    }
+                    println!("Damping: {:?}", damping);
+                }
+                Command::ToggleForceModel => {
+                    let fem = simulation.world.cloths.first().is_some_and(|cloth| cloth.force_model == ForceModel::Fem);
+                    let force_model = if fem { ForceModel::Springs } else { ForceModel::Fem };
+                    // cloths without elements keep their springs
+                    for cloth in simulation.world.cloths.iter_mut().filter(|cloth| cloth.fem.is_some()) {
+                        cloth.force_model = force_model;
+                    }
+                    println!("Force Model: {:?}", force_model);
+                }
+                Command::ToggleWind => {
+                    let windy = simulation.world.cloths.first().is_some_and(|cloth| cloth.aerodynamics.is_some());
+                    for cloth in &mut simulation.world.cloths {
+                        cloth.aerodynamics = if windy { None } else { Some(Aerodynamics::default()) };
+                    }
+                    println!("Wind: {}", !windy);
+                }
+                Command::ToggleClothCollisions => {
+                    simulation.world.collide_cloths = !simulation.world.collide_cloths;
+                    println!("Cloth Collisions: {}", simulation.world.collide_cloths);
+                }
+                Command::DropBody(body) => {
+                    simulation.world.bodies.push(body);
+                }
+                Command::AddCloth(cloth) => {
+                    simulation.add_cloth(*cloth);
+                    layout = Arc::new(simulation.world.layout());
+                }
+                Command::Grab { cloth, point, x, y } => {
+                    simulation.release();
+
+                    let was_fixed = simulation.world.cloths
+                        .get(cloth)
+                        .and_then(|cloth| cloth.points.get(point))
+                        .map(|point| point.fixed);
+                    if let Some(was_fixed) = was_fixed {
+                        simulation.grab = Some(Grab { cloth, point, target: [x, y], was_fixed });
+                    }
+                }
+                Command::DragTo { x, y } => {
+                    if let Some(grab) = &mut simulation.grab {
+                        grab.target = [x, y];
+                    }
+                }
+                Command::Release => {
+                    simulation.release();
+                }
+                Command::HeatGun(target) => {
+                    simulation.heat_gun = target;
+                }
+                Command::ToggleAdaptive => {
+                    simulation.adaptive = !simulation.adaptive;
+                    println!("Adaptive Timestep: {}", simulation.adaptive);
+                }
+                Command::Save(path) => {
+                    match simulation.world.save(&path) {
+                        Ok(()) => println!("Saved snapshot to {}", path.display()),
+                        Err(e) => println!("Failed to save snapshot to {}: {}", path.display(), e),
+                    }
+                }
+                Command::Load(path) => {
+                    match World::load(&path) {
+                        Ok(world) => {
+                            simulation.load(world);
+                            layout = Arc::new(simulation.world.layout());
+                            steps += 1;
+                            println!("Loaded snapshot from {}", path.display());
+                        }
+                        Err(e) => println!("Failed to load snapshot from {}: {}", path.display(), e),
+                    }
+                }
+                Command::ToggleRecording { dir, every } => {
+                    match simulation.recorder.take() {
+                        Some(recorder) => {
+                            match recorder.export(&dir) {
+                                Ok(()) =>
+                                    println!(
+                                        "Exported {} frames to {}",
+                                        recorder.num_frames(),
+                                        dir.display()
+                                    ),
+                                Err(e) => println!("Failed to export recording: {}", e),
+                            }
+                        }
+                        None => {
+                            let mut recorder = Recorder::new(every, timestep.dt);
+                            recorder.capture(&simulation.world);
+                            simulation.recorder = Some(recorder);
+                            println!("Recording");
+                        }
+                    }
+                }
+                Command::Quit => {
+                    return simulation.stats;
+                }
+            }
+        }
+
+        let now = Instant::now();
+        let frame_time = now.duration_since(last_time);
+        last_time = now;
+
+        // a paused simulation shows exactly the state it stopped on
+        let alpha = if paused {
+            1.0
+        } else {
+            let due = timestep.advance(frame_time.as_secs_f32());
+            for _ in 0..due {
+                // stop on the last good state so it can be inspected or rewound
+                if let Err(e) = simulation.step(timestep.dt) {
+                    println!("Simulation diverged, pausing: {}", e);
+                    paused = true;
+                    break;
+                }
+            }
+            steps += due;
+
+            timestep.alpha()
+        };
+
+        // building a snapshot walks every spring, so only rebuild when the world moved and
+        // otherwise hand out the last one with the new alpha
+        step += steps as u64;
+        if steps > 0 || changed {
+            latest = Snapshot::new(
+                &simulation.world,
+                simulation.prev_positions.clone(),
+                alpha,
+                step,
+                &layout,
+                heatmap
+            );
+            snapshots.write(latest.clone());
+        } else if !paused {
+            snapshots.write(Snapshot { alpha, ..latest.clone() });
+        }
+
+        // nothing to do until another step is due, don't spin a core on it
+        if steps == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
@@ -0,0 +1,259 @@
+// spring forces over structure-of-arrays copies of the cloth, so the same field of
+// consecutive springs sits side by side in memory and eight springs can be worked on at once.
+// Nothing here depends on the rest of the crate, the benchmarks include this file directly
+
+use rayon::prelude::*;
+#[cfg(feature = "simd")]
+use wide::{ f32x8, CmpLe };
+
+// the parts of each point the springs read
+#[derive(Clone, Debug, Default)]
+pub struct PointArrays {
+    pub x: Vec<f32>,
+    pub y: Vec<f32>,
+    pub vx: Vec<f32>,
+    pub vy: Vec<f32>,
+    pub temperature: Vec<f32>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SpringArrays {
+    pub p1: Vec<usize>,
+    pub p2: Vec<usize>,
+    pub rest_length: Vec<f32>,
+    pub spring_coeff: Vec<f32>,
+    pub damp_coeff: Vec<f32>,
+    pub stiffening_strain: Vec<f32>,
+    pub stiffening_ratio: Vec<f32>,
+    pub thermal_expansion: Vec<f32>,
+}
+
+// force on the first point of springs `first..first + forces.len()`, the second point gets the
+// opposite. Matches `Spring::force`, with the dampers included only when `relative_damping`
+// since legacy dampers act on each end separately
+pub fn spring_forces(
+    points: &PointArrays,
+    springs: &SpringArrays,
+    relative_damping: bool,
+    first: usize,
+    forces: &mut [(f32, f32)]
+) {
+    #[cfg(feature = "simd")]
+    spring_forces_simd(points, springs, relative_damping, first, forces);
+
+    #[cfg(not(feature = "simd"))]
+    spring_forces_scalar(points, springs, relative_damping, first, forces);
+}
+
+pub fn spring_forces_scalar(
+    points: &PointArrays,
+    springs: &SpringArrays,
+    relative_damping: bool,
+    first: usize,
+    forces: &mut [(f32, f32)]
+) {
+    for (offset, force) in forces.iter_mut().enumerate() {
+        let i = first + offset;
+        let (a, b) = (springs.p1[i], springs.p2[i]);
+
+        let dx = points.x[b] - points.x[a];
+        let dy = points.y[b] - points.y[a];
+        let distance = (dx * dx + dy * dy).sqrt();
+
+        let temperature = (points.temperature[a] + points.temperature[b]) / 2.0;
+        let rest_length = springs.rest_length[i] * (1.0 + springs.thermal_expansion[i] * temperature).max(0.1);
+
+        let extension = distance - rest_length;
+        let knee = springs.stiffening_strain[i] * rest_length;
+        let mut magnitude = if extension <= knee {
+            springs.spring_coeff[i] * extension
+        } else {
+            springs.spring_coeff[i] * (knee + (extension - knee) * springs.stiffening_ratio[i])
+        };
+
+        if relative_damping {
+            let separating = ((points.vx[b] - points.vx[a]) * dx + (points.vy[b] - points.vy[a]) * dy) / distance;
+            magnitude += springs.damp_coeff[i] * separating;
+        }
+
+        *force = ((magnitude * dx) / distance, (magnitude * dy) / distance);
+    }
+}
+
+// the same sums eight springs at a time. Points can't be loaded as a vector since each lane
+// wants a different one, so they're gathered lane by lane and only the arithmetic is wide. The
+// leftover springs at the end go through the scalar version
+#[cfg(feature = "simd")]
+pub fn spring_forces_simd(
+    points: &PointArrays,
+    springs: &SpringArrays,
+    relative_damping: bool,
+    first: usize,
+    forces: &mut [(f32, f32)]
+) {
+    const LANES: usize = 8;
+
+    let whole = forces.len() - forces.len() % LANES;
+    let (wide_forces, rest_forces) = forces.split_at_mut(whole);
+
+    let load = |values: &[f32], i: usize| -> f32x8 {
+        f32x8::from(<[f32; LANES]>::try_from(&values[i..i + LANES]).unwrap())
+    };
+    let gather = |values: &[f32], indices: &[usize]| -> f32x8 {
+        f32x8::from(std::array::from_fn::<f32, LANES, _>(|lane| values[indices[lane]]))
+    };
+
+    for (block, out) in wide_forces.chunks_exact_mut(LANES).enumerate() {
+        let i = first + block * LANES;
+        let a = &springs.p1[i..i + LANES];
+        let b = &springs.p2[i..i + LANES];
+
+        let dx = gather(&points.x, b) - gather(&points.x, a);
+        let dy = gather(&points.y, b) - gather(&points.y, a);
+        let distance = (dx * dx + dy * dy).sqrt();
+
+        let temperature = (gather(&points.temperature, a) + gather(&points.temperature, b)) * f32x8::splat(0.5);
+        let scale = (f32x8::ONE + load(&springs.thermal_expansion, i) * temperature).max(f32x8::splat(0.1));
+        let rest_length = load(&springs.rest_length, i) * scale;
+
+        let spring_coeff = load(&springs.spring_coeff, i);
+        let extension = distance - rest_length;
+        let knee = load(&springs.stiffening_strain, i) * rest_length;
+
+        // both sides of the knee, keeping whichever applies per lane
+        let linear = spring_coeff * extension;
+        let stiffened = spring_coeff * (knee + (extension - knee) * load(&springs.stiffening_ratio, i));
+        let mut magnitude = extension.cmp_le(knee).blend(linear, stiffened);
+
+        if relative_damping {
+            let dvx = gather(&points.vx, b) - gather(&points.vx, a);
+            let dvy = gather(&points.vy, b) - gather(&points.vy, a);
+            let separating = (dvx * dx + dvy * dy) / distance;
+            magnitude += load(&springs.damp_coeff, i) * separating;
+        }
+
+        let fx = ((magnitude * dx) / distance).to_array();
+        let fy = ((magnitude * dy) / distance).to_array();
+        for (lane, force) in out.iter_mut().enumerate() {
+            *force = (fx[lane], fy[lane]);
+        }
+    }
+
+    spring_forces_scalar(points, springs, relative_damping, first + whole, rest_forces);
+}
+
+// spring `i`'s force from `spring_forces` as the forces on its two ends. Legacy dampers are
+// added here since they pull on each end by its own velocity, see `Spring::force`
+fn end_forces(
+    points: &PointArrays,
+    springs: &SpringArrays,
+    i: usize,
+    (fx, fy): (f32, f32),
+    relative_damping: bool
+) -> [(f32, f32); 2] {
+    let mut forces = [(fx, fy), (-fx, -fy)];
+
+    if !relative_damping {
+        for (force, end) in forces.iter_mut().zip([springs.p1[i], springs.p2[i]]) {
+            force.0 -= points.vx[end] * springs.damp_coeff[i];
+            force.1 -= points.vy[end] * springs.damp_coeff[i];
+        }
+    }
+
+    forces
+}
+
+// total spring force on every point, each point looking through all the springs for the ones
+// it's on. Nothing is shared between threads, but every point reads every spring
+pub fn gather(
+    points: &PointArrays,
+    springs: &SpringArrays,
+    forces: &[(f32, f32)],
+    relative_damping: bool
+) -> Vec<(f32, f32)> {
+    (0..points.x.len())
+        .into_par_iter()
+        .map(|index| {
+            let mut total = (0.0, 0.0);
+
+            for (i, &force) in forces.iter().enumerate() {
+                let end = if springs.p1[i] == index {
+                    0
+                } else if springs.p2[i] == index {
+                    1
+                } else {
+                    continue;
+                };
+
+                let (fx, fy) = end_forces(points, springs, i, force, relative_damping)[end];
+                total.0 += fx;
+                total.1 += fy;
+            }
+
+            total
+        })
+        .collect()
+}
+
+// the spring force totals, shared between the threads working through one colour
+#[derive(Clone, Copy)]
+struct SharedTotals(*mut (f32, f32));
+
+// only ever written through `add`, whose callers keep threads on separate points
+unsafe impl Send for SharedTotals {}
+unsafe impl Sync for SharedTotals {}
+
+impl SharedTotals {
+    // Safety: `index` is in bounds of the totals and no other thread reads or writes it until
+    // this returns
+    unsafe fn add(self, index: usize, (fx, fy): (f32, f32)) {
+        let total = &mut *self.0.add(index);
+        total.0 += fx;
+        total.1 += fy;
+    }
+}
+
+// total spring force on every point, one colour after another with each colour's springs
+// spread over the threads. Every spring adds straight into its two ends, and since no two
+// springs of a colour share a point no locks or atomics are needed. `colours` lists spring
+// indices, see `Colouring` in cloth.rs. Panics if two springs of a colour share a point or an
+// index is out of range
+pub fn scatter(
+    points: &PointArrays,
+    springs: &SpringArrays,
+    forces: &[(f32, f32)],
+    colours: &[Vec<usize>],
+    relative_damping: bool
+) -> Vec<(f32, f32)> {
+    let num_points = points.x.len();
+
+    // the last colour to touch each point, one pass over the springs makes the writes below safe
+    let mut owner = vec![usize::MAX; num_points];
+    for (colour, springs_of_colour) in colours.iter().enumerate() {
+        for &i in springs_of_colour {
+            for end in [springs.p1[i], springs.p2[i]] {
+                assert!(end < num_points, "spring {} ends past the points", i);
+                assert!(owner[end] != colour, "colour {} repeats point {}", colour, end);
+                owner[end] = colour;
+            }
+        }
+    }
+
+    let mut totals = vec![(0.0, 0.0); num_points];
+    let shared = SharedTotals(totals.as_mut_ptr());
+
+    for colour in colours {
+        colour.par_iter().for_each(|&i| {
+            let [force1, force2] = end_forces(points, springs, i, forces[i], relative_damping);
+
+            // SAFETY: checked above, both ends are below `num_points` and no other spring of
+            // this colour, so no other thread, touches them
+            unsafe {
+                shared.add(springs.p1[i], force1);
+                shared.add(springs.p2[i], force2);
+            }
+        });
+    }
+
+    totals
+}
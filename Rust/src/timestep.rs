@@ -0,0 +1,64 @@
+// fixed-timestep accumulator so the simulation rate does not depend on the frame rate
+pub struct FixedTimestep {
+    pub dt: f32,
+    pub max_substeps: usize,
+    pub time_scale: f32,
+    accumulator: f32,
+}
+
+impl FixedTimestep {
+    pub fn new(dt: f32, max_substeps: usize) -> Self {
+        FixedTimestep {
+            dt,
+            max_substeps,
+            time_scale: 1.0,
+            accumulator: 0.0,
+        }
+    }
+
+    // add a frame's worth of (scaled) wall time and return how many steps of `dt` to run
+    pub fn advance(&mut self, frame_time: f32) -> usize {
+        self.accumulator += frame_time * self.time_scale;
+
+        let mut steps = (self.accumulator / self.dt) as usize;
+        self.accumulator -= (steps as f32) * self.dt;
+
+        // if we can't keep up, drop the backlog instead of falling further behind every frame
+        if steps > self.max_substeps {
+            steps = self.max_substeps;
+        }
+        self.accumulator = self.accumulator.min(self.dt);
+
+        steps
+    }
+
+    // how far between the previous and current state the renderer should draw, 0..=1
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.dt).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_long_frame_runs_at_most_max_substeps() {
+        let mut timestep = FixedTimestep::new(0.01, 5);
+        assert_eq!(timestep.advance(1.0), 5);
+        // the backlog is dropped rather than carried into the next frame
+        assert!(timestep.advance(0.0) <= 1);
+        assert!((0.0..=1.0).contains(&timestep.alpha()));
+    }
+
+    #[test]
+    fn alpha_is_the_leftover_fraction_of_a_step() {
+        let mut timestep = FixedTimestep::new(0.01, 5);
+        assert_eq!(timestep.advance(0.025), 2);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-3);
+
+        timestep.time_scale = 0.0;
+        assert_eq!(timestep.advance(1.0), 0);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-3);
+    }
+}
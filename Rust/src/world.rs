@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+use rayon::prelude::*;
+use serde::{ Deserialize, Serialize };
+
+use crate::cloth::{ self, Cloth, Diagnostics, Point, SimError, StepStats };
+use crate::rigid::{ self, RigidBody };
+
+// how close points of two different cloths may get before they push each other apart
+const CLOTH_THICKNESS: f32 = 0.5;
+
+// a point as (cloth, index)
+type PointId = (usize, usize);
+
+// colours handed out to cloths in a generated scene
+pub const PALETTE: [[f32; 4]; 4] = [
+    [0.0, 0.0, 0.0, 1.0],
+    [0.8, 0.15, 0.15, 1.0],
+    [0.1, 0.5, 0.15, 1.0],
+    [0.15, 0.25, 0.8, 1.0],
+];
+
+// where one cloth's points sit in the world's flattened positions, for drawing and picking
+#[derive(Clone, Debug)]
+pub struct ClothLayout {
+    pub first_point: usize,
+    pub num_points: usize,
+    // local to the cloth, add `first_point` for world indices
+    pub indices: Vec<u32>,
+    pub color: [f32; 4],
+}
+
+// every cloth in the scene plus the rigid bodies falling onto them. Cloths step independently
+// and in parallel, then bodies and optionally the cloths themselves are collided
+#[derive(Clone, Serialize, Deserialize)]
+pub struct World {
+    pub cloths: Vec<Cloth>,
+    #[serde(default)]
+    pub bodies: Vec<RigidBody>,
+    #[serde(default)]
+    pub collide_cloths: bool,
+}
+
+impl From<Cloth> for World {
+    fn from(cloth: Cloth) -> Self {
+        World::new(vec![cloth])
+    }
+}
+
+impl World {
+    pub fn new(cloths: Vec<Cloth>) -> Self {
+        World {
+            cloths,
+            bodies: Vec::new(),
+            collide_cloths: false,
+        }
+    }
+
+    // JSON for a .json path and bincode otherwise, like `Cloth::save`
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        cloth::write_snapshot(path.as_ref(), self)
+    }
+
+    // also accepts a single cloth saved before there were worlds
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+
+        // a single cloth's snapshot loads as a world of one, if it's neither say why for both
+        let world = match cloth::read_snapshot(path) {
+            Ok(world) => world,
+            Err(world_error) => {
+                let cloth = Cloth::load(path).map_err(|cloth_error| {
+                    io::Error::new(
+                        cloth_error.kind(),
+                        format!("not a world snapshot ({}) or a cloth snapshot ({})", world_error, cloth_error)
+                    )
+                })?;
+                World::from(cloth)
+            }
+        };
+
+        if world.cloths.is_empty() {
+            return Err(cloth::invalid_data("snapshot contains no points"));
+        }
+        for cloth in &world.cloths {
+            cloth.validate().map_err(cloth::invalid_data)?;
+        }
+
+        Ok(world)
+    }
+
+    // bodies fall with the first cloth's gravity
+    fn gravity(&self) -> f32 {
+        self.cloths
+            .first()
+            .map_or(0.0, |cloth| if cloth.g_on { cloth.g } else { 0.0 })
+    }
+
+    pub fn set_gravity(&mut self, g_on: bool) {
+        for cloth in &mut self.cloths {
+            cloth.g_on = g_on;
+        }
+    }
+
+    // warm every cloth around (x, y), see `Cloth::heat`
+    pub fn heat(&mut self, x: f32, y: f32, radius: f32, rate: f32, dt: f32) {
+        for cloth in &mut self.cloths {
+            cloth.heat(x, y, radius, rate, dt);
+        }
+    }
+
+    // every cloth's points one after another, in `layout` order
+    pub fn positions(&self) -> Vec<[f32; 2]> {
+        self.cloths.iter().flat_map(Cloth::positions).collect()
+    }
+
+    pub fn spring_indices(&self) -> Vec<u32> {
+        self.layout()
+            .iter()
+            .flat_map(|layout| layout.indices.iter().map(|&i| i + (layout.first_point as u32)))
+            .collect()
+    }
+
+    pub fn layout(&self) -> Vec<ClothLayout> {
+        let mut first_point = 0;
+
+        self.cloths
+            .iter()
+            .map(|cloth| {
+                let num_points = cloth.points.len();
+                let layout = ClothLayout {
+                    first_point,
+                    num_points,
+                    indices: cloth.spring_indices(),
+                    color: cloth.color,
+                };
+                first_point += num_points;
+                layout
+            })
+            .collect()
+    }
+
+    // a world index from `positions` as (cloth, point index within it)
+    pub fn locate(layout: &[ClothLayout], index: usize) -> Option<(usize, usize)> {
+        layout
+            .iter()
+            .enumerate()
+            .find(|(_, cloth)| (cloth.first_point..cloth.first_point + cloth.num_points).contains(&index))
+            .map(|(c, cloth)| (c, index - cloth.first_point))
+    }
+
+    pub fn diagnostics(&self) -> Diagnostics {
+        let mut diagnostics = self.cloths
+            .iter()
+            .map(Cloth::diagnostics)
+            .fold(Diagnostics::default(), |total, d| Diagnostics {
+                kinetic: total.kinetic + d.kinetic,
+                potential: total.potential + d.potential,
+                elastic: total.elastic + d.elastic,
+                momentum: (total.momentum.0 + d.momentum.0, total.momentum.1 + d.momentum.1),
+                max_strain: total.max_strain.max(d.max_strain),
+            });
+
+        let gravity = self.gravity();
+        for body in &self.bodies {
+            diagnostics.kinetic += body.kinetic_energy();
+            diagnostics.potential += body.mass * gravity * body.y;
+            diagnostics.momentum.0 += body.mass * body.vx;
+            diagnostics.momentum.1 += body.mass * body.vy;
+        }
+
+        diagnostics
+    }
+
+    pub fn simulate_multithreaded(&mut self, dt: f32) -> Result<StepStats, SimError> {
+        self.step_each(dt, |cloth| cloth.simulate_multithreaded(dt).map(|()| StepStats { dt, substeps: 1 }))
+    }
+
+    pub fn step_with_recovery(&mut self, dt: f32, max_halvings: u32) -> Result<StepStats, SimError> {
+        self.step_each(dt, |cloth| cloth.step_with_recovery(dt, max_halvings))
+    }
+
+    pub fn advance(&mut self, frame_time: f32, max_substeps: usize) -> Result<StepStats, SimError> {
+        self.step_each(frame_time, |cloth| cloth.advance(frame_time, max_substeps))
+    }
+
+    // step the cloths in parallel, then the bodies and contacts once over the whole `dt`. The
+    // stats are the smallest dt and most substeps any cloth needed. If any cloth fails every
+    // cloth goes back to where it was, so the world is left whole at the start of the step
+    fn step_each(
+        &mut self,
+        dt: f32,
+        step: impl Fn(&mut Cloth) -> Result<StepStats, SimError> + Sync + Send
+    ) -> Result<StepStats, SimError> {
+        let before = self.cloths.clone();
+        let results: Vec<Result<StepStats, SimError>> = self.cloths.par_iter_mut().map(step).collect();
+
+        let mut stats = StepStats { dt, substeps: 1 };
+        for result in results {
+            let cloth_stats = match result {
+                Ok(cloth_stats) => cloth_stats,
+                Err(error) => {
+                    self.cloths = before;
+                    return Err(error);
+                }
+            };
+            stats.dt = stats.dt.min(cloth_stats.dt);
+            stats.substeps = stats.substeps.max(cloth_stats.substeps);
+        }
+
+        let gravity = self.gravity();
+        rigid::integrate(&mut self.bodies, dt, gravity);
+        for cloth in &mut self.cloths {
+            rigid::collide(&mut self.bodies, &mut cloth.points);
+        }
+
+        if self.collide_cloths {
+            self.resolve_cloth_contacts();
+        }
+
+        Ok(stats)
+    }
+
+    // push apart points of different cloths that are closer than `CLOTH_THICKNESS`, exchanging
+    // their approach velocity like two equal balls. Points are bucketed into a grid of
+    // thickness sized cells so only neighbouring cells are compared
+    fn resolve_cloth_contacts(&mut self) {
+        let cell_of = |point: &Point| {
+            ((point.x / CLOTH_THICKNESS).floor() as i32, (point.y / CLOTH_THICKNESS).floor() as i32)
+        };
+
+        let mut grid: HashMap<(i32, i32), Vec<PointId>> = HashMap::new();
+        for (c, cloth) in self.cloths.iter().enumerate() {
+            for (i, point) in cloth.points.iter().enumerate() {
+                grid.entry(cell_of(point)).or_default().push((c, i));
+            }
+        }
+
+        let mut pairs = Vec::new();
+        for (&(cx, cy), cell) in &grid {
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    let Some(neighbour) = grid.get(&(cx + dx, cy + dy)) else {
+                        continue;
+                    };
+
+                    // each pair once, and only between different cloths
+                    for &a in cell {
+                        for &b in neighbour {
+                            if a.0 < b.0 {
+                                pairs.push((a, b));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for ((c1, i1), (c2, i2)) in pairs {
+            let p1 = self.cloths[c1].points[i1];
+            let p2 = self.cloths[c2].points[i2];
+
+            let dx = p2.x - p1.x;
+            let dy = p2.y - p1.y;
+            let distance = (dx * dx + dy * dy).sqrt();
+            if distance >= CLOTH_THICKNESS || distance == 0.0 {
+                continue;
+            }
+
+            let (nx, ny) = (dx / distance, dy / distance);
+            let inv_mass1 = if p1.fixed { 0.0 } else { 1.0 / p1.mass };
+            let inv_mass2 = if p2.fixed { 0.0 } else { 1.0 / p2.mass };
+            let inv_mass_sum = inv_mass1 + inv_mass2;
+            if inv_mass_sum == 0.0 {
+                continue;
+            }
+
+            let depth = CLOTH_THICKNESS - distance;
+            let closing = ((p2.vx - p1.vx) * nx + (p2.vy - p1.vy) * ny).min(0.0);
+            let impulse = -closing / inv_mass_sum;
+
+            let point1 = &mut self.cloths[c1].points[i1];
+            point1.x -= nx * depth * (inv_mass1 / inv_mass_sum);
+            point1.y -= ny * depth * (inv_mass1 / inv_mass_sum);
+            point1.vx -= impulse * nx * inv_mass1;
+            point1.vy -= impulse * ny * inv_mass1;
+
+            let point2 = &mut self.cloths[c2].points[i2];
+            point2.x += nx * depth * (inv_mass2 / inv_mass_sum);
+            point2.y += ny * depth * (inv_mass2 / inv_mass_sum);
+            point2.vx += impulse * nx * inv_mass2;
+            point2.vy += impulse * ny * inv_mass2;
+        }
+    }
+}